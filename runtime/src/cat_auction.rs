@@ -1,12 +1,13 @@
 use support::{ decl_module, decl_storage, decl_event, dispatch::Result,
   StorageValue, StorageMap, ensure, traits::{ Currency, ReservableCurrency } };
-use { system::ensure_signed, timestamp };
+use { system::{ ensure_signed, ensure_root }, timestamp };
 
 // this is needed when you want to use Vec and Box
 use rstd::prelude::*;
-use runtime_primitives::traits::{ As, /*CheckedAdd, CheckedDiv, CheckedMul,*/ Hash };
+use runtime_primitives::{ Permill, traits::{ As, CheckedAdd, /*CheckedDiv, CheckedMul,*/ Hash } };
 use parity_codec::{ Encode, Decode };
-// use runtime_io::{ self };
+#[cfg(feature = "std")]
+use serde::Serialize;
 
 pub type StdResult<T> = rstd::result::Result<T, &'static str>;
 
@@ -15,14 +16,227 @@ pub trait Trait: timestamp::Trait + balances::Trait {
   type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
 }
 
-// store the 3 topmost bids, and they cannot be withdrawn
-const TOPMOST_BIDS_LEN: usize = 3;
-// auction duration has to be at least 3 mins
-const AUCTION_MIN_DURATION: u64 = 3 * 60;
-// modify the following to at least 1 min when run in production
-const DISPLAY_BIDS_UPDATE_PERIOD: u64 = 1 * 60;
+// genesis defaults of the auction parameters root can tune, see `TopmostBidsLen`,
+//   `AuctionMinDuration` and `DisplayBidsUpdatePeriod`
+const DEFAULT_TOPMOST_BIDS_LEN: u32 = 3;
+const DEFAULT_AUCTION_MIN_DURATION: u64 = 3 * 60;
+const DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD: u64 = 1 * 60;
+// topmost bids are sorted on every bid, so their number is kept small
+const MAX_TOPMOST_BIDS_LEN: u32 = 10;
+// bounds of the auction min duration root can set. Auctions are settled in buckets of
+//   `SCHEDULE_BUCKET_SECS`, so a shorter min duration buys nothing
+const MIN_AUCTION_MIN_DURATION: u64 = SCHEDULE_BUCKET_SECS;
+const MAX_AUCTION_MIN_DURATION: u64 = 30 * 24 * 60 * 60;
+// display bids left stale longer than this defeat their purpose
+const MAX_DISPLAY_BIDS_UPDATE_PERIOD: u64 = 24 * 60 * 60;
+// auctions are queued for automatic settlement and display bids refresh in buckets
+//   of their scheduled time
+const SCHEDULE_BUCKET_SECS: u64 = 60;
+// auctions settled in one block at most, so block weight stays bounded. Leftovers
+//   are carried over to the next block
+const MAX_SETTLEMENTS_PER_BLOCK: usize = 10;
+// expiry buckets visited in one block at most
+const MAX_EXPIRY_BUCKETS_PER_BLOCK: u64 = 16;
+// auctions having their display bids refreshed in one block at most
+const MAX_DISPLAY_REFRESHES_PER_BLOCK: usize = 10;
+// display refresh buckets visited in one block at most
+const MAX_DISPLAY_REFRESH_BUCKETS_PER_BLOCK: u64 = 16;
+// breeding cooldown after the first breeding of a kitty. It doubles on every breeding
+const BREED_COOLDOWN_BASE: u64 = 1 * 60;
+// breeding cooldown cannot grow beyond this
+const BREED_COOLDOWN_MAX: u64 = 7 * 24 * 60 * 60;
+// percentage of the deposit forfeited to the seller when a sealed bid is never revealed
+const SEALED_BID_FORFEIT_PERCENT: u32 = 50;
+// creator royalty on a kitty cannot be set higher than this percentage of a sale
+const MAX_ROYALTY_PERCENT: u32 = 50;
+// the treasury account collecting protocol fees is derived from this seed
+const TREASURY_SEED: &[u8] = b"cat_auction/treasury";
+// percentage of the listing deposit slashed to the treasury when the seller cancels the auction
+const CANCEL_DEPOSIT_SLASH_PERCENT: u32 = 50;
+// storage layout version of this module. Bump it with a migration step in `_migrate_storage`
+//   whenever the encoding of a stored struct or a storage index changes.
+//   v1: the baseline `Kitty`, `Auction` and `Bid`, with `Kitty.owner_pos` and the `OwnerKitties`
+//     index. Storage before versioning is v1
+//   v2: `OwnedKitties` linked lists, and `Kitty`, `Auction` and `Bid` with their current attributes
+const STORAGE_VERSION: u32 = 2;
+// stored values migrated in one block at most, so block weight stays bounded. The rest
+//   are carried over to the next block
+const MAX_MIGRATIONS_PER_BLOCK: u64 = 20;
+// items returned in one page of a runtime API query at most
+const MAX_QUERY_PAGE_LEN: u64 = 100;
+
+/// Reasons `start_auction`, `bid`, `cancel_auction` and `close_auction_and_tx` reject a call.
+///
+/// Dispatch results are `&'static str` in this runtime, so the error is carried as its message,
+///   which always starts with `CatAuction#<code>:`. The discriminant is the stable code of the
+///   error: variants are only appended, and a code is never reused.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize))]
+#[repr(u8)]
+pub enum Error {
+  KittyNotExist = 1,
+  NotKittyOwner = 2,
+  KittyInAuction = 3,
+  KittySiringLocked = 4,
+  KittyListed = 5,
+  EndTimeTooSoon = 6,
+  BasePriceZero = 7,
+  AuctionIdExists = 8,
+  SoftCloseZero = 9,
+  SoftCloseBeforeEndTime = 10,
+  BuyNowBelowBasePrice = 11,
+  ListingDepositUnaffordable = 12,
+  AuctionNotExist = 13,
+  NotAuctionAdmin = 14,
+  AuctionEnded = 15,
+  AuctionHasBids = 16,
+  KittyNoOwner = 17,
+  OwnerCannotBid = 18,
+  BidBelowBasePrice = 19,
+  AuctionNotActive = 20,
+  NotEnglishAuction = 21,
+  AuctionExpired = 22,
+  BidIncrementTooLow = 23,
+  BidNotAbovePrevious = 24,
+  BidIdExists = 25,
+  BidUnaffordable = 26,
+  AuctionNotExpired = 27,
+  StorageMigrating = 28,
+  BuyNowNotEnglish = 29,
+  NoBuyNowPrice = 30,
+  OwnerCannotBuy = 31,
+  BuyUnaffordable = 32,
+  NotDutchAuction = 33,
+  NotSealedAuction = 34,
+  RevealTooEarly = 35,
+  RevealPeriodOver = 36,
+  NoBidInAuction = 37,
+  BidRevealed = 38,
+  CommitmentMismatch = 39,
+  DisplayBidsRecentlyUpdated = 40,
+  WithdrawNotEnglish = 41,
+  BidNotActive = 42,
+  DisplayedBidWithdrawal = 43,
+  RevealBeforeEndTime = 44,
+  SealedDepositZero = 45,
+  StartPriceNotAboveFloor = 46,
+  SiringDurationZero = 47,
+  BidIncrementOverflow = 48,
+  BuyNowOutbid = 49,
+  BuyerCannotPay = 50,
+  SellerCannotReceive = 51,
+  PaymentFailed = 52,
+  BidDepositUnaffordable = 53,
+  TopmostBidsLenOutOfRange = 54,
+  AuctionMinDurationOutOfRange = 55,
+  DisplayPeriodOutOfRange = 56,
+}
+
+impl Error {
+  /// All errors, in the order of their codes.
+  pub const ALL: [Error; 56] = [
+    Error::KittyNotExist, Error::NotKittyOwner, Error::KittyInAuction, Error::KittySiringLocked,
+    Error::KittyListed, Error::EndTimeTooSoon, Error::BasePriceZero, Error::AuctionIdExists,
+    Error::SoftCloseZero, Error::SoftCloseBeforeEndTime, Error::BuyNowBelowBasePrice,
+    Error::ListingDepositUnaffordable, Error::AuctionNotExist, Error::NotAuctionAdmin,
+    Error::AuctionEnded, Error::AuctionHasBids, Error::KittyNoOwner, Error::OwnerCannotBid,
+    Error::BidBelowBasePrice, Error::AuctionNotActive, Error::NotEnglishAuction,
+    Error::AuctionExpired, Error::BidIncrementTooLow, Error::BidNotAbovePrevious,
+    Error::BidIdExists, Error::BidUnaffordable, Error::AuctionNotExpired, Error::StorageMigrating,
+    Error::BuyNowNotEnglish, Error::NoBuyNowPrice, Error::OwnerCannotBuy, Error::BuyUnaffordable,
+    Error::NotDutchAuction, Error::NotSealedAuction, Error::RevealTooEarly, Error::RevealPeriodOver,
+    Error::NoBidInAuction, Error::BidRevealed, Error::CommitmentMismatch,
+    Error::DisplayBidsRecentlyUpdated, Error::WithdrawNotEnglish, Error::BidNotActive,
+    Error::DisplayedBidWithdrawal, Error::RevealBeforeEndTime, Error::SealedDepositZero,
+    Error::StartPriceNotAboveFloor, Error::SiringDurationZero, Error::BidIncrementOverflow,
+    Error::BuyNowOutbid, Error::BuyerCannotPay, Error::SellerCannotReceive, Error::PaymentFailed,
+    Error::BidDepositUnaffordable, Error::TopmostBidsLenOutOfRange,
+    Error::AuctionMinDurationOutOfRange, Error::DisplayPeriodOutOfRange,
+  ];
+
+  pub fn code(&self) -> u8 { *self as u8 }
+
+  /// The message returned in the dispatch result, starting with the error code.
+  pub fn message(&self) -> &'static str {
+    match self {
+      Error::KittyNotExist => "CatAuction#1: Kitty does not exist",
+      Error::NotKittyOwner => "CatAuction#2: You are not the kitty owner",
+      Error::KittyInAuction => "CatAuction#3: Kitty is already in another auction",
+      Error::KittySiringLocked => "CatAuction#4: Kitty is locked by an outstanding siring right",
+      Error::KittyListed => "CatAuction#5: Kitty is listed at a fixed price",
+      Error::EndTimeTooSoon => "CatAuction#6: End time cannot be set less than the auction min duration from current time",
+      Error::BasePriceZero => "CatAuction#7: Base price must be set greater than 0",
+      Error::AuctionIdExists => "CatAuction#8: Auction ID generated exists already",
+      Error::SoftCloseZero => "CatAuction#9: Soft close window and extension must be set greater than 0",
+      Error::SoftCloseBeforeEndTime =>
+        "CatAuction#10: Soft close max end time cannot be before the auction end time",
+      Error::BuyNowBelowBasePrice => "CatAuction#11: Buy-it-now price cannot be lower than the base price",
+      Error::ListingDepositUnaffordable => "CatAuction#12: Not enough free balance for the listing deposit",
+      Error::AuctionNotExist => "CatAuction#13: Auction does not exist",
+      Error::NotAuctionAdmin => "CatAuction#14: You are not the auction admin",
+      Error::AuctionEnded => "CatAuction#15: The auction has passed its end time",
+      Error::AuctionHasBids => "CatAuction#16: Someone has bidded already. So this auction cannot be cancelled",
+      Error::KittyNoOwner => "CatAuction#17: Kitty does not have owner",
+      Error::OwnerCannotBid => "CatAuction#18: The kitty owner cannot bid in this auction",
+      Error::BidBelowBasePrice => "CatAuction#19: The bid price is lower than the auction base price",
+      Error::AuctionNotActive => "CatAuction#20: Auction is not active",
+      Error::NotEnglishAuction => "CatAuction#21: Only english auction accepts open bids",
+      Error::AuctionExpired => "CatAuction#22: Auction has expired already",
+      Error::BidIncrementTooLow =>
+        "CatAuction#23: The bid does not beat the current leading bid by the minimum increment",
+      Error::BidNotAbovePrevious => "CatAuction#24: New bid has to be larger than your previous bid",
+      Error::BidIdExists => "CatAuction#25: Generated bid ID is duplicated",
+      Error::BidUnaffordable => "CatAuction#26: Not enough free balance to reserve for the bid",
+      Error::AuctionNotExpired => "CatAuction#27: The auction is not expired yet",
+      Error::StorageMigrating => "CatAuction#28: Storage migration to the current layout is in progress",
+      Error::BuyNowNotEnglish => "CatAuction#29: Only english auction can be bought now",
+      Error::NoBuyNowPrice => "CatAuction#30: Auction has no buy-it-now price",
+      Error::OwnerCannotBuy => "CatAuction#31: The kitty owner cannot buy in this auction",
+      Error::BuyUnaffordable => "CatAuction#32: Not enough balance to buy the kitty",
+      Error::NotDutchAuction => "CatAuction#33: Auction is not a dutch auction",
+      Error::NotSealedAuction => "CatAuction#34: Auction is not a sealed-bid auction",
+      Error::RevealTooEarly => "CatAuction#35: Bids cannot be revealed before the auction end time",
+      Error::RevealPeriodOver => "CatAuction#36: The reveal period has passed already",
+      Error::NoBidInAuction => "CatAuction#37: You have no bid in this auction",
+      Error::BidRevealed => "CatAuction#38: Your bid is revealed already",
+      Error::CommitmentMismatch => "CatAuction#39: The revealed bid does not match the commitment",
+      Error::DisplayBidsRecentlyUpdated =>
+        "CatAuction#40: The auction display bids has just been recently updated",
+      Error::WithdrawNotEnglish => "CatAuction#41: Only bids in english auction can be withdrawn",
+      Error::BidNotActive => "CatAuction#42: Your bid is no longer active",
+      Error::DisplayedBidWithdrawal => "CatAuction#43: Displayed topmost bids cannot be withdrawn",
+      Error::RevealBeforeEndTime => "CatAuction#44: Reveal end time must be after the auction end time",
+      Error::SealedDepositZero => "CatAuction#45: Sealed bid deposit must be set greater than 0",
+      Error::StartPriceNotAboveFloor => "CatAuction#46: Start price must be greater than the floor price",
+      Error::SiringDurationZero => "CatAuction#47: Siring right duration must be set greater than 0",
+      Error::BidIncrementOverflow => "CatAuction#48: The leading bid plus the min increment overflows",
+      Error::BuyNowOutbid => "CatAuction#49: The leading bid has reached the buy-it-now price",
+      Error::BuyerCannotPay => "CatAuction#50: The buyer cannot pay the auction price",
+      Error::SellerCannotReceive => "CatAuction#51: The seller account cannot receive the payment",
+      Error::PaymentFailed => "CatAuction#52: Fund transfer error",
+      Error::BidDepositUnaffordable =>
+        "CatAuction#53: Not enough free balance to reserve for the sealed bid deposit",
+      Error::TopmostBidsLenOutOfRange =>
+        "CatAuction#54: Topmost bids length must be set between 1 and the max topmost bids length",
+      Error::AuctionMinDurationOutOfRange =>
+        "CatAuction#55: Auction min duration must be set between one schedule bucket and 30 days",
+      Error::DisplayPeriodOutOfRange =>
+        "CatAuction#56: Display bids update period must be set between 1s and one day",
+    }
+  }
+
+  /// The error a dispatch result message carries, if it is one of this module.
+  pub fn from_message(message: &str) -> Option<Error> {
+    Self::ALL.iter().find(|err| err.message() == message).cloned()
+  }
+}
+
+impl From<Error> for &'static str {
+  fn from(err: Error) -> &'static str { err.message() }
+}
 
 #[derive(Encode, Decode, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize))]
 pub enum AuctionStatus {
   Ongoing,
   Cancelled,
@@ -34,10 +248,29 @@ impl Default for AuctionStatus {
   fn default() -> Self { AuctionStatus::Ongoing }
 }
 
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize))]
+pub enum AuctionKind {
+  // open ascending-price auction, bid prices are known once placed
+  English,
+  // bidders commit to a hidden price first, and reveal it after the auction end time
+  Sealed,
+  // descending price auction, the first one accepting the current price buys the kitty
+  Dutch,
+}
+// necessary so structs depending on this enum can be en-/de-code with
+//   default value.
+impl Default for AuctionKind {
+  fn default() -> Self { AuctionKind::English }
+}
+
 #[derive(Encode, Decode, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize))]
 pub enum BidStatus {
   Active,
   Withdrawn,
+  // a sealed bid that has not been revealed yet
+  Committed,
 }
 // necessary so structs depending on this enum can be en-/de-code with
 //   default value.
@@ -45,17 +278,149 @@ impl Default for BidStatus {
   fn default() -> Self { BidStatus::Active }
 }
 
+// How the auction winner price is determined when the auction closes
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize))]
+pub enum PricingRule {
+  // winner pays his own bid
+  FirstPrice,
+  // winner pays the second highest bid, but not less than the base price
+  SecondPrice,
+}
+// necessary so structs depending on this enum can be en-/de-code with
+//   default value.
+impl Default for PricingRule {
+  fn default() -> Self { PricingRule::FirstPrice }
+}
+
+// Anti-sniping rule of an auction: a bid placed within `window` before the auction end time
+//   pushes the end time forward by `extension`, but not beyond `max_end_time`
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize))]
+pub struct SoftClose<Moment> {
+  window: Moment,
+  extension: Moment,
+  max_end_time: Moment,
+}
+
+// What the auction winner gets
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize))]
+pub enum AuctionItem {
+  // the kitty ownership
+  Ownership,
+  // a one-time right to breed with the kitty as a sire, the kitty stays with its owner
+  SiringRight,
+}
+// necessary so structs depending on this enum can be en-/de-code with
+//   default value.
+impl Default for AuctionItem {
+  fn default() -> Self { AuctionItem::Ownership }
+}
+
+// A siring right sold in a siring auction. The kitty cannot be transferred until the right
+//   is used or expires.
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq, Debug)]
+pub struct SiringRight<AccountId, Moment> {
+  grantee: AccountId,
+  expires: Moment,
+}
+
+// Minimum amount a new bid has to beat the current leading bid by
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize))]
+pub enum BidIncrement<Balance> {
+  Absolute(Balance),
+  // percentage of the leading bid price
+  Percentage(Permill),
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize))]
+pub enum Coat {
+  Tabby,
+  Calico,
+  Tuxedo,
+  Siamese,
+  Sphynx,
+}
+// necessary so structs depending on this enum can be en-/de-code with
+//   default value.
+impl Default for Coat {
+  fn default() -> Self { Coat::Tabby }
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize))]
+pub enum Rarity {
+  Common,
+  Uncommon,
+  Rare,
+  Legendary,
+}
+// necessary so structs depending on this enum can be en-/de-code with
+//   default value.
+impl Default for Rarity {
+  fn default() -> Self { Rarity::Common }
+}
+
+// Kitty attributes derived from its DNA
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize))]
+pub struct KittyTraits {
+  coat: Coat,
+  // RGB color in 0xRRGGBB
+  color: u32,
+  rarity: Rarity,
+}
+
+impl KittyTraits {
+  // decode kitty traits from its DNA. Byte 0 is the coat, bytes 1-3 the color, and
+  //   byte 4 the rarity tier.
+  pub fn from_dna(dna: &[u8]) -> Self {
+    let gene = |i: usize| dna.get(i).cloned().unwrap_or(0);
+
+    let coat = match gene(0) % 5 {
+      0 => Coat::Tabby,
+      1 => Coat::Calico,
+      2 => Coat::Tuxedo,
+      3 => Coat::Siamese,
+      _ => Coat::Sphynx,
+    };
+    let color = (gene(1) as u32) << 16 | (gene(2) as u32) << 8 | gene(3) as u32;
+    let rarity = match gene(4) {
+      0..=127 => Rarity::Common,
+      128..=191 => Rarity::Uncommon,
+      192..=239 => Rarity::Rare,
+      _ => Rarity::Legendary,
+    };
+
+    KittyTraits { coat, color, rarity }
+  }
+}
+
 // Our own Cat struct
-#[derive(Encode, Decode, Default, Clone, PartialEq, Debug)]
-pub struct Kitty<Hash, AccountId> {
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize))]
+pub struct Kitty<Hash, AccountId, Moment> {
   id: Hash,
   name: Option<Vec<u8>>,
   owner: Option<AccountId>,
-  owner_pos: Option<u64>,
   in_auction: bool,
+  dna: Hash,
+  traits: KittyTraits,
+  // breeding: the parents and generation of the kitty, and when it can breed again
+  parents: Option<(Hash, Hash)>,
+  generation: u64,
+  breed_count: u32,
+  cooldown_end: Moment,
+  // the account minting the kitty, and its share of every later sale
+  creator: Option<AccountId>,
+  royalty: Permill,
 }
 
-#[derive(Encode, Decode, Default, Clone, PartialEq, Debug)]
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize))]
 pub struct Auction<Hash, Balance, Moment, AuctionTx> {
   id: Hash,
   kitty_id: Hash,
@@ -63,6 +428,23 @@ pub struct Auction<Hash, Balance, Moment, AuctionTx> {
   start_time: Moment,
   end_time: Moment,
   status: AuctionStatus,
+  pricing_rule: PricingRule,
+  kind: AuctionKind,
+  // only used in sealed-bid auction: bids are revealed between `end_time` and `reveal_end_time`
+  reveal_end_time: Moment,
+  sealed_deposit: Balance,
+  // reserved from the seller when the auction starts, returned when it closes
+  listing_deposit: Balance,
+  soft_close: Option<SoftClose<Moment>>,
+  min_increment: Option<BidIncrement<Balance>>,
+  buy_now_price: Option<Balance>,
+  // only used in dutch auction: the price falls from `dutch_start_price` at `start_time` to
+  //   `base_price` at `end_time`, linearly or every `dutch_step` when it is not zero
+  dutch_start_price: Balance,
+  dutch_step: Moment,
+  item: AuctionItem,
+  // only used in siring auction: how long the siring right sold lasts
+  siring_right_duration: Moment,
 
   topmost_bids: Vec<Hash>,
   price_to_topmost: Balance,
@@ -73,6 +455,7 @@ pub struct Auction<Hash, Balance, Moment, AuctionTx> {
 }
 
 #[derive(Encode, Decode, Default, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize))]
 pub struct Bid<Hash, AccountId, Balance, Moment> {
   id: Hash,
   auction_id: Hash,
@@ -80,31 +463,279 @@ pub struct Bid<Hash, AccountId, Balance, Moment> {
   price: Balance,
   last_update: Moment,
   status: BidStatus,
+  // only used in sealed-bid auction
+  commitment: Option<Hash>,
+  deposit: Balance,
 }
 
 #[derive(Encode, Decode, Default, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize))]
 pub struct AuctionTx<Moment, AccountId, Balance> {
   tx_time: Moment,
   winner: AccountId,
   tx_price: Balance,
+  // part of `tx_price` paid to the treasury as protocol fee, and to the kitty creator
+  fee: Balance,
+  royalty: Balance,
+}
+
+// Storage v1 layouts of `Kitty`, `Auction`, `AuctionTx` and `Bid`, as stored before storage
+//   versioning. Only used to migrate values already in storage
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct KittyV1<Hash, AccountId> {
+  id: Hash,
+  name: Option<Vec<u8>>,
+  owner: Option<AccountId>,
+  owner_pos: Option<u64>,
+  in_auction: bool,
+}
+
+impl<Hash: AsRef<[u8]> + Clone, AccountId> KittyV1<Hash, AccountId> {
+  // return the kitty in v2 layout, and its position in the v1 `OwnerKitties` index. A v1 kitty
+  //   has no DNA, so its random ID is taken as its DNA. Nobody takes a royalty on it
+  fn upgrade<Moment: Default>(self) -> (Kitty<Hash, AccountId, Moment>, Option<u64>) {
+    let kitty = Kitty {
+      id: self.id.clone(),
+      name: self.name,
+      owner: self.owner,
+      in_auction: self.in_auction,
+      traits: KittyTraits::from_dna(self.id.as_ref()),
+      dna: self.id,
+      parents: None,
+      generation: 0,
+      breed_count: 0,
+      cooldown_end: Default::default(),
+      creator: None,
+      royalty: Permill::zero(),
+    };
+    (kitty, self.owner_pos)
+  }
+}
+
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct AuctionV1<Hash, Balance, Moment, AuctionTx> {
+  id: Hash,
+  kitty_id: Hash,
+  base_price: Balance,
+  start_time: Moment,
+  end_time: Moment,
+  status: AuctionStatus,
+
+  topmost_bids: Vec<Hash>,
+  price_to_topmost: Balance,
+  display_bids: Vec<Hash>,
+  display_bids_last_update: Moment,
+
+  tx: Option<AuctionTx>,
+}
+
+impl<Hash, Balance: Default, Moment: Default, AccountId>
+  AuctionV1<Hash, Balance, Moment, AuctionTxV1<Moment, AccountId, Balance>>
+{
+  // return the auction in v2 layout. A v1 auction is an english first-price auction of the kitty
+  fn upgrade(self) -> Auction<Hash, Balance, Moment, AuctionTx<Moment, AccountId, Balance>> {
+    Auction {
+      id: self.id,
+      kitty_id: self.kitty_id,
+      base_price: self.base_price,
+      start_time: self.start_time,
+      end_time: self.end_time,
+      status: self.status,
+      pricing_rule: PricingRule::FirstPrice,
+      kind: AuctionKind::English,
+      reveal_end_time: Default::default(),
+      sealed_deposit: Default::default(),
+      listing_deposit: Default::default(),
+      soft_close: None,
+      min_increment: None,
+      buy_now_price: None,
+      dutch_start_price: Default::default(),
+      dutch_step: Default::default(),
+      item: AuctionItem::Ownership,
+      siring_right_duration: Default::default(),
+
+      topmost_bids: self.topmost_bids,
+      price_to_topmost: self.price_to_topmost,
+      display_bids: self.display_bids,
+      display_bids_last_update: self.display_bids_last_update,
+
+      tx: self.tx.map(|tx| AuctionTx {
+        tx_time: tx.tx_time,
+        winner: tx.winner,
+        tx_price: tx.tx_price,
+        fee: Default::default(),
+        royalty: Default::default(),
+      }),
+    }
+  }
+}
+
+#[derive(Encode, Decode, Default, Clone, PartialEq, Debug)]
+pub struct AuctionTxV1<Moment, AccountId, Balance> {
+  tx_time: Moment,
+  winner: AccountId,
+  tx_price: Balance,
+}
+
+#[derive(Encode, Decode, Default, Clone, PartialEq, Debug)]
+pub struct BidV1<Hash, AccountId, Balance, Moment> {
+  id: Hash,
+  auction_id: Hash,
+  bidder: AccountId,
+  price: Balance,
+  last_update: Moment,
+  status: BidStatus,
+}
+
+impl<Hash, AccountId, Balance: Default, Moment> BidV1<Hash, AccountId, Balance, Moment> {
+  // return the bid in v2 layout. A v1 bid is an open bid
+  fn upgrade(self) -> Bid<Hash, AccountId, Balance, Moment> {
+    Bid {
+      id: self.id,
+      auction_id: self.auction_id,
+      bidder: self.bidder,
+      price: self.price,
+      last_update: self.last_update,
+      status: self.status,
+      commitment: None,
+      deposit: Default::default(),
+    }
+  }
+}
+
+// A node in the per-owner doubly linked list of kitties. The node keyed by `None` is the
+//   list head: its `next` is the first kitty and its `prev` the last one
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq, Debug)]
+pub struct KittyLinkedItem<Hash> {
+  prev: Option<Hash>,
+  next: Option<Hash>,
+}
+
+// A standing offer on a kitty, with the offer price reserved from the offerer
+#[derive(Encode, Decode, Default, Clone, PartialEq, Debug)]
+pub struct Offer<Hash, AccountId, Balance, Moment> {
+  id: Hash,
+  kitty_id: Hash,
+  offerer: AccountId,
+  price: Balance,
+  expiry: Moment,
+  // positions in `KittyOffers` and `OffererOffers`
+  kitty_pos: u64,
+  offerer_pos: u64,
+}
+
+// An auction with the kitty in auction and its displayed bids, as returned by the runtime API
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize))]
+pub struct AuctionDetail<Kitty, Auction, Bid> {
+  auction: Auction,
+  kitty: Kitty,
+  display_bids: Vec<Bid>,
+  bids_count: u64,
 }
 
+type KittyOf<T> = Kitty<<T as system::Trait>::Hash, <T as system::Trait>::AccountId,
+  <T as timestamp::Trait>::Moment>;
+type KittyV1Of<T> = KittyV1<<T as system::Trait>::Hash, <T as system::Trait>::AccountId>;
+type AuctionV1Of<T> = AuctionV1<<T as system::Trait>::Hash, <T as balances::Trait>::Balance,
+  <T as timestamp::Trait>::Moment, AuctionTxV1<<T as timestamp::Trait>::Moment,
+  <T as system::Trait>::AccountId, <T as balances::Trait>::Balance>>;
+type BidV1Of<T> = BidV1<<T as system::Trait>::Hash, <T as system::Trait>::AccountId,
+  <T as balances::Trait>::Balance, <T as timestamp::Trait>::Moment>;
+type AuctionOf<T> = Auction<<T as system::Trait>::Hash, <T as balances::Trait>::Balance,
+  <T as timestamp::Trait>::Moment, AuctionTxOf<T>>;
+type AuctionTxOf<T> = AuctionTx<<T as timestamp::Trait>::Moment, <T as system::Trait>::AccountId,
+  <T as balances::Trait>::Balance>;
+type BidOf<T> = Bid<<T as system::Trait>::Hash, <T as system::Trait>::AccountId,
+  <T as balances::Trait>::Balance, <T as timestamp::Trait>::Moment>;
+type AuctionDetailOf<T> = AuctionDetail<KittyOf<T>, AuctionOf<T>, BidOf<T>>;
+type OfferOf<T> = Offer<<T as system::Trait>::Hash, <T as system::Trait>::AccountId,
+  <T as balances::Trait>::Balance, <T as timestamp::Trait>::Moment>;
+
 // This module's storage items.
 decl_storage! {
   trait Store for Module<T: Trait> as CatAuction {
-    Kitties get(kitties): map T::Hash => Kitty<T::Hash, T::AccountId>;
+    Kitties get(kitties): map T::Hash => KittyOf<T>;
     KittiesArray get(kitty_array): map u64 => T::Hash;
     KittiesCount get(kitties_count): u64 = 0;
 
-    // The following two go hand-in-hand, write to one likely need to update the other two
-    OwnerKitties get(owner_kitties): map (T::AccountId, u64) => T::Hash;
+    // Kitties of an owner as a linked list: (owner, kitty_id) => its neighbours in the list,
+    //   (owner, None) => the list head. Use `owned_kitty_ids` to enumerate them
+    OwnedKitties get(owned_kitties): map (T::AccountId, Option<T::Hash>) => Option<KittyLinkedItem<T::Hash>>;
     OwnerKittiesCount get(owner_kitties_count): map T::AccountId => u64 = 0;
 
+    // Storage layout version, and how far the migration to the next version has gone
+    StorageVersion get(storage_version): u32;
+    //   The cursor is (index over kitties then auctions, index over the bids of the auction)
+    MigrationCursor get(migration_cursor): (u64, u64);
+
+    // `kitty_id` => the account allowed to breed with the kitty once, besides its owner
+    SiringApprovals get(siring_approval): map T::Hash => Option<T::AccountId>;
+    // `kitty_id` => the siring right sold in a siring auction
+    SiringRights get(siring_right): map T::Hash => Option<SiringRight<T::AccountId, T::Moment>>;
+
+    // Fixed-price marketplace: `kitty_id` => the price the kitty is listed at
+    KittyPrices get(kitty_price): map T::Hash => Option<T::Balance>;
+
+    // `offer_id` => Offer object
+    Offers get(offers): map T::Hash => OfferOf<T>;
+    // On kitty & offer: (kitty_id, index) => offer_id
+    KittyOffers get(kitty_offers): map (T::Hash, u64) => T::Hash;
+    KittyOffersCount get(kitty_offers_count): map T::Hash => u64 = 0;
+    // On offerer & offer: (offerer, index) => offer_id
+    OffererOffers get(offerer_offers): map (T::AccountId, u64) => T::Hash;
+    OffererOffersCount get(offerer_offers_count): map T::AccountId => u64 = 0;
+
+    // Share of every auction settlement paid to the treasury account. Only set by root
+    ProtocolFee get(protocol_fee): Permill;
+    // Total protocol fees the treasury account has collected
+    TreasuryRevenue get(treasury_revenue): T::Balance;
+    // Total listing deposits of cancelled auctions the treasury account has collected
+    TreasurySlashes get(treasury_slashes): T::Balance;
+    // Deposit the seller reserves to start an auction. Only set by root
+    ListingDeposit get(listing_deposit): T::Balance;
+
+    // Auction parameters. Only set by root
+    // The number of topmost bids of an auction. They cannot be withdrawn
+    TopmostBidsLen get(topmost_bids_len) config(): u32 = DEFAULT_TOPMOST_BIDS_LEN;
+    // An auction has to run for at least this long, in seconds
+    AuctionMinDuration get(auction_min_duration) config(): u64 = DEFAULT_AUCTION_MIN_DURATION;
+    // Display bids of an auction are refreshed once in this period, in seconds
+    DisplayBidsUpdatePeriod get(display_bids_update_period) config(): u64 = DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD;
+
+    // Family tree: (parent kitty_id, index) => child kitty_id
+    KittyChildren get(kitty_children): map (T::Hash, u64) => T::Hash;
+    KittyChildrenCount get(kitty_children_count): map T::Hash => u64 = 0;
+
     // On Auction
     Auctions get(auctions): map T::Hash => Auction<T::Hash, T::Balance, T::Moment,
       AuctionTx<T::Moment, T::AccountId, T::Balance>>;
     AuctionsArray get(auction_array): map u64 => T::Hash;
     AuctionsCount get(auctions_count): u64 = 0;
+    // Ongoing auctions, paged over by queries. An auction leaving it is replaced by the last one
+    OngoingAuctions get(ongoing_auction): map u64 => T::Hash;
+    OngoingAuctionsCount get(ongoing_auctions_count): u64 = 0;
+    OngoingAuctionsIndex get(ongoing_auctions_index): map T::Hash => Option<u64>;
+
+    // Auction settlement queue: (close time bucket, index) => auction id closing within the bucket.
+    //   Entries are kept one by one, so a block only touches the entries it settles
+    AuctionExpiryQueue get(auction_expiry_queue): map (u64, u64) => T::Hash;
+    AuctionExpiryQueueLen get(auction_expiry_queue_len): map u64 => u64;
+    // (The earliest close time bucket not fully settled yet, the index of its next entry).
+    //   None when nothing has been queued
+    AuctionExpiryCursor get(auction_expiry_cursor): Option<(u64, u64)>;
+
+    // Display bids refresh schedule: (refresh time bucket, index) => auction id to refresh
+    //   within the bucket
+    DisplayRefreshQueue get(display_refresh_queue): map (u64, u64) => T::Hash;
+    DisplayRefreshQueueLen get(display_refresh_queue_len): map u64 => u64;
+    // (The earliest refresh time bucket not fully processed yet, the index of its next entry).
+    //   None when nothing has been queued
+    DisplayRefreshCursor get(display_refresh_cursor): Option<(u64, u64)>;
+    // `auction_id` => the time its display bids are refreshed next
+    NextDisplayRefresh get(next_display_refresh): map T::Hash => Option<T::Moment>;
 
     // `bid_id` => Bid object
     Bids get(bids): map T::Hash => Bid<T::Hash, T::AccountId, T::Balance, T::Moment>;
@@ -113,9 +744,31 @@ decl_storage! {
     AuctionBids get(auction_bids): map (T::Hash, u64) => T::Hash;
     AuctionBidsCount get(auction_bids_count): map T::Hash => u64 = 0;
     AuctionBidderBids get(auction_bidder_bids): map (T::Hash, T::AccountId) => T::Hash;
+    // Bids of a bidder not withdrawn, paged over by queries. A withdrawn bid is replaced by the
+    //   last one of the bidder
+    BidderBids get(bidder_bid): map (T::AccountId, u64) => T::Hash;
+    BidderBidsCount get(bidder_bids_count): map T::AccountId => u64 = 0;
+    BidderBidsIndex get(bidder_bids_index): map T::Hash => Option<u64>;
 
     Nonce: u64 = 0;
   }
+  add_extra_genesis {
+    // kitties minted at genesis: (owner, name, dna)
+    config(kitties): Vec<(T::AccountId, Vec<u8>, T::Hash)>;
+
+    build(|storage: &mut runtime_primitives::StorageOverlay,
+      _: &mut runtime_primitives::ChildrenStorageOverlay, config: &GenesisConfig<T>|
+    {
+      runtime_io::with_storage(storage, || {
+        for (owner, kitty_name, dna) in config.kitties.iter() {
+          <Module<T>>::_mint_kitty(owner, kitty_name.clone(), *dna, None, 0, Permill::zero())
+            .expect("genesis kitty ids are generated from a fresh nonce; qed");
+        }
+        // genesis kitties are stored in the current layout, so there is nothing to migrate
+        <StorageVersion<T>>::put(STORAGE_VERSION);
+      });
+    });
+  }
 }
 
 decl_event!(
@@ -127,10 +780,33 @@ decl_event!(
 
     // Events in our runtime
     KittyCreated(AccountId, Hash, Vec<u8>),
+    KittyTransferred(AccountId, AccountId, Hash),
+    SiringApprovalSet(Hash, Option<AccountId>),
+    KittyBred(AccountId, Hash, Hash, Hash),
+    SiringRightGranted(Hash, AccountId, Moment),
+    KittyPriceSet(AccountId, Hash, Option<Balance>),
+    KittyBought(AccountId, AccountId, Hash, Balance),
+    OfferMade(AccountId, Hash, Hash, Balance, Moment),
+    OfferAccepted(Hash, AccountId, AccountId, Balance),
+    OfferCancelled(Hash),
+    OfferCleared(Hash),
+    RoyaltyPaid(Hash, AccountId, Balance),
+    ProtocolFeeSet(Permill),
+    ProtocolFeeCollected(Hash, Balance),
+    ListingDepositSet(Balance),
+    TopmostBidsLenSet(u32),
+    AuctionMinDurationSet(u64),
+    DisplayBidsUpdatePeriodSet(u64),
+    ListingDepositSlashed(Hash, Balance),
+    StorageMigrated(u32),
     AuctionStarted(AccountId, Hash, Hash, Balance, Moment),
     AuctionCancelled(Hash),
+    AuctionExtended(Hash, Moment),
     AuctionClosed(Hash),
     NewBid(Hash, Balance),
+    BidCommitted(Hash, Hash),
+    BidRevealed(Hash, Hash, Balance),
+    BidWithdrawn(Hash, Hash, AccountId),
     UpdateDisplayedBids(Hash, Vec<Hash>),
     AuctionTx(Hash, Hash, AccountId, AccountId),
   }
@@ -140,88 +816,397 @@ decl_module! {
   pub struct Module<T: Trait> for enum Call where origin: T::Origin {
     fn deposit_event<T>() = default;
 
-    pub fn create_kitty(origin, kitty_name: Vec<u8>) -> Result {
-      let sender = ensure_signed(origin)?;
+    fn on_initialize(_n: T::BlockNumber) {
+      Self::_migrate_storage();
+    }
 
-      let kitty_id = Self::_gen_random_hash(&sender)?;
-      // ensure the kitty_id is not existed
-      ensure!(!<Kitties<T>>::exists(&kitty_id), "Cat with the id existed already");
+    fn on_finalize(_n: T::BlockNumber) {
+      // queued auctions are read in the current layout
+      if Self::storage_version() != STORAGE_VERSION { return; }
 
-      let mut kitty = Kitty {
-        id: kitty_id,
-        name: Some(kitty_name.clone()),
-        owner: None,      // to be updated in _add_kitty_to_storage
-        owner_pos: None,  // to be updated in _add_kitty_to_storage
-        in_auction: false,
-      };
-      Self::_add_kitty_to_storage(&mut kitty, Some(&sender))?;
+      Self::_refresh_due_display_bids();
+      Self::_settle_expired_auctions();
+    }
 
-      // emit an event
-      Self::deposit_event(RawEvent::KittyCreated(sender, kitty_id, kitty_name));
+    pub fn create_kitty(origin, kitty_name: Vec<u8>, royalty: Permill) -> Result {
+      Self::_ensure_storage_migrated()?;
+      let sender = ensure_signed(origin)?;
+      ensure!(royalty <= Permill::from_percent(MAX_ROYALTY_PERCENT),
+        "Royalty cannot be set higher than the max royalty percent");
+
+      let dna = Self::_gen_dna(&sender);
+      Self::_mint_kitty(&sender, kitty_name, dna, None, 0, royalty)?;
       Ok(())
     } // end of fn `create_kitty`
 
-    pub fn start_auction(origin, kitty_id: T::Hash, end_time: T::Moment,
-      base_price: T::Balance) -> Result {
+    pub fn transfer_kitty(origin, to: T::AccountId, kitty_id: T::Hash) -> Result {
+      Self::_ensure_storage_migrated()?;
+      let sender = ensure_signed(origin)?;
+      // Check:
+      //  1. kitty exists, and the sender is the kitty owner
+      //  2. kitty is not in auction
+      //  3. not transferring to himself
+
+      // check #1
+      ensure!(<Kitties<T>>::exists(kitty_id), "Kitty does not exist");
+      let kitty = Self::kitties(kitty_id);
+      ensure!(kitty.owner == Some(sender.clone()), "You are not the kitty owner");
 
+      // check #2
+      ensure!(!kitty.in_auction, "Kitty is in an auction");
+      ensure!(!Self::_siring_locked(&kitty_id, &<timestamp::Module<T>>::get()),
+        "Kitty is locked by an outstanding siring right");
+
+      // check #3
+      ensure!(to != sender, "Cannot transfer the kitty to yourself");
+
+      Self::_transfer_kitty_ownership(&kitty_id, &to)?;
+
+      Self::deposit_event(RawEvent::KittyTransferred(sender, to, kitty_id));
+      Ok(())
+    } // end of fn `transfer_kitty`
+
+    pub fn approve_siring(origin, kitty_id: T::Hash, to: Option<T::AccountId>) -> Result {
+      Self::_ensure_storage_migrated()?;
+      let sender = ensure_signed(origin)?;
+
+      ensure!(<Kitties<T>>::exists(kitty_id), "Kitty does not exist");
+      ensure!(Self::kitties(kitty_id).owner == Some(sender.clone()), "You are not the kitty owner");
+
+      match to {
+        Some(ref account) => <SiringApprovals<T>>::insert(kitty_id, account.clone()),
+        None => <SiringApprovals<T>>::remove(kitty_id),
+      }
+
+      Self::deposit_event(RawEvent::SiringApprovalSet(kitty_id, to));
+      Ok(())
+    } // end of fn `approve_siring`
+
+    pub fn set_price(origin, kitty_id: T::Hash, price: Option<T::Balance>) -> Result {
+      Self::_ensure_storage_migrated()?;
       let sender = ensure_signed(origin)?;
       // Check:
-      //  1. ensure kitty exists, and the kitty.owner == sender. Currently,
-      //     only the kitty owner can put his own kitty in auction
-      //  2. kitty is not already `in_auction` state
-      //  3. ensure end_time > current_time
-      //  4. base_price > 0
+      //  1. kitty exists, and the sender is the kitty owner
+      //  2. kitty is not in auction, nor locked by a siring right
+      //  3. price > 0 when listing the kitty. `None` takes the kitty off the market
 
       // check #1
       ensure!(<Kitties<T>>::exists(kitty_id), "Kitty does not exist");
       let kitty = Self::kitties(kitty_id);
+      ensure!(kitty.owner == Some(sender.clone()), "You are not the kitty owner");
 
       // check #2
-      ensure!(!kitty.in_auction, "Kitty is already in another auction");
+      ensure!(!kitty.in_auction, "Kitty is in an auction");
+      ensure!(!Self::_siring_locked(&kitty_id, &<timestamp::Module<T>>::get()),
+        "Kitty is locked by an outstanding siring right");
 
       // check #3
-      let now = <timestamp::Module<T>>::get();
-      ensure!(end_time.clone().as_() > AUCTION_MIN_DURATION + now.clone().as_(),
-        "End time cannot be set less than 3 mins from current time");
+      match price {
+        Some(price) => {
+          ensure!(price > <T::Balance as As<u64>>::sa(0), "Price must be set greater than 0");
+          <KittyPrices<T>>::insert(kitty_id, price);
+        },
+        None => <KittyPrices<T>>::remove(kitty_id),
+      }
+
+      Self::deposit_event(RawEvent::KittyPriceSet(sender, kitty_id, price));
+      Ok(())
+    } // end of fn `set_price`
+
+    pub fn buy_kitty(origin, kitty_id: T::Hash, max_price: T::Balance) -> Result {
+      Self::_ensure_storage_migrated()?;
+      let sender = ensure_signed(origin)?;
+      // Check:
+      //  1. kitty exists, and is listed at a fixed price
+      //  2. the buyer is not the kitty owner
+      //  3. the listed price does not exceed `max_price`, so the seller cannot raise the
+      //     price on the buyer while the purchase is in flight
+      //  4. the buyer has enough free balance to pay
+
+      // check #1
+      ensure!(<Kitties<T>>::exists(kitty_id), "Kitty does not exist");
+      let price = Self::kitty_price(kitty_id).ok_or("Kitty is not for sale")?;
+      let kitty_owner = Self::kitties(kitty_id).owner.ok_or("Kitty has no owner")?;
+
+      // check #2
+      ensure!(kitty_owner != sender, "You already own the kitty");
+
+      // check #3
+      ensure!(price <= max_price, "Kitty price is higher than the max price you offered");
 
       // check #4
-      ensure!(base_price > <T::Balance as As<u64>>::sa(0),
-        "Base price must be set greater than 0");
+      ensure!(<balances::Module<T>>::free_balance(&sender) >= price,
+        "You don't have enough free balance to buy the kitty");
 
-      // Write:
-      //  1. create the auction
-      let auction_id = Self::_gen_random_hash(&sender)?;
-      // check: auction_id not existed yet
-      ensure!(!<Auctions<T>>::exists(&auction_id), "Auction ID generated exists already");
+      Self::_pay_seller(&sender, &kitty_owner, &kitty_id, price, false)?;
+      // the listing is removed as part of the ownership transfer
+      Self::_transfer_kitty_ownership(&kitty_id, &sender)?;
 
-      let auction = Auction {
-        id: auction_id.clone(),
-        kitty_id,
-        base_price,
-        start_time: now.clone(),
-        end_time: end_time.clone(),
-        status: AuctionStatus::Ongoing,
+      Self::deposit_event(RawEvent::KittyBought(sender, kitty_owner, kitty_id, price));
+      Ok(())
+    } // end of fn `buy_kitty`
+
+    pub fn make_offer(origin, kitty_id: T::Hash, price: T::Balance, expiry: T::Moment) -> Result {
+      Self::_ensure_storage_migrated()?;
+      let sender = ensure_signed(origin)?;
+      // Check:
+      //  1. kitty exists, and the sender is not the kitty owner
+      //  2. price > 0
+      //  3. expiry > current time
+
+      // check #1
+      ensure!(<Kitties<T>>::exists(kitty_id), "Kitty does not exist");
+      ensure!(Self::kitties(kitty_id).owner != Some(sender.clone()),
+        "You cannot make an offer on your own kitty");
+
+      // check #2
+      ensure!(price > <T::Balance as As<u64>>::sa(0), "Offer price must be set greater than 0");
+
+      // check #3
+      ensure!(expiry > <timestamp::Module<T>>::get(), "Offer expiry must be in the future");
 
-        topmost_bids: Vec::new(),
-        price_to_topmost: base_price,
-        display_bids: Vec::new(),
-        display_bids_last_update: now,
+      let offer_id = Self::_gen_random_hash(&sender)?;
+      ensure!(!<Offers<T>>::exists(&offer_id), "Offer ID generated exists already");
 
-        tx: None,
+      // write:
+      //   1. offerer money has to be locked before the offer is stored
+      //   2. store the offer and index it per kitty and per offerer
+      <balances::Module<T>>::reserve(&sender, price)?;
+
+      let kitty_pos = Self::kitty_offers_count(kitty_id);
+      let offerer_pos = Self::offerer_offers_count(&sender);
+      let offer = Offer {
+        id: offer_id,
+        kitty_id,
+        offerer: sender.clone(),
+        price,
+        expiry: expiry.clone(),
+        kitty_pos,
+        offerer_pos,
       };
 
-      Self::_add_auction_to_storage(&auction)?;
+      <Offers<T>>::insert(offer_id, offer);
+      <KittyOffers<T>>::insert((kitty_id, kitty_pos), offer_id);
+      <KittyOffersCount<T>>::insert(kitty_id, kitty_pos + 1);
+      <OffererOffers<T>>::insert((sender.clone(), offerer_pos), offer_id);
+      <OffererOffersCount<T>>::insert(&sender, offerer_pos + 1);
+
+      Self::deposit_event(RawEvent::OfferMade(sender, offer_id, kitty_id, price, expiry));
+      Ok(())
+    } // end of fn `make_offer`
 
-      // also set the kitty state in_auction = true
-      <Kitties<T>>::mutate(kitty_id, |k| k.in_auction = true);
+    pub fn accept_offer(origin, offer_id: T::Hash) -> Result {
+      Self::_ensure_storage_migrated()?;
+      let sender = ensure_signed(origin)?;
+      // Check:
+      //  1. offer exists, and the sender is the kitty owner
+      //  2. offer has not expired
+      //  3. kitty is not in auction, nor locked by a siring right
 
-      // emit an event
-      Self::deposit_event(RawEvent::AuctionStarted(sender, kitty_id, auction_id,
-        base_price, end_time));
+      // check #1
+      ensure!(<Offers<T>>::exists(offer_id), "Offer does not exist");
+      let offer = Self::offers(offer_id);
+      let kitty = Self::kitties(offer.kitty_id);
+      ensure!(kitty.owner == Some(sender.clone()), "You are not the kitty owner");
+
+      // check #2
+      let now = <timestamp::Module<T>>::get();
+      ensure!(offer.expiry > now, "Offer has expired already");
+
+      // check #3
+      ensure!(!kitty.in_auction, "Kitty is in an auction");
+      ensure!(!Self::_siring_locked(&offer.kitty_id, &now),
+        "Kitty is locked by an outstanding siring right");
+
+      // write:
+      //   1. the reserved offer money goes to the kitty owner, and the kitty creator royalty
+      //   2. transfer the kitty ownership
+      //   3. remove the offer
+      Self::_pay_seller(&offer.offerer, &sender, &offer.kitty_id, offer.price, true)?;
+      Self::_transfer_kitty_ownership(&offer.kitty_id, &offer.offerer)?;
+      Self::_remove_offer(&offer);
+
+      Self::deposit_event(RawEvent::OfferAccepted(offer_id, sender, offer.offerer, offer.price));
+      Ok(())
+    } // end of fn `accept_offer`
+
+    pub fn cancel_offer(origin, offer_id: T::Hash) -> Result {
+      Self::_ensure_storage_migrated()?;
+      let sender = ensure_signed(origin)?;
+
+      ensure!(<Offers<T>>::exists(offer_id), "Offer does not exist");
+      let offer = Self::offers(offer_id);
+      ensure!(offer.offerer == sender, "You are not the offerer");
+
+      <balances::Module<T>>::unreserve(&sender, offer.price);
+      Self::_remove_offer(&offer);
+
+      Self::deposit_event(RawEvent::OfferCancelled(offer_id));
+      Ok(())
+    } // end of fn `cancel_offer`
+
+    // anyone can clean up an expired offer, the offer money returns to the offerer
+    pub fn clear_expired_offer(_origin, offer_id: T::Hash) -> Result {
+      Self::_ensure_storage_migrated()?;
+      ensure!(<Offers<T>>::exists(offer_id), "Offer does not exist");
+      let offer = Self::offers(offer_id);
+      ensure!(offer.expiry <= <timestamp::Module<T>>::get(), "Offer has not expired yet");
+
+      <balances::Module<T>>::unreserve(&offer.offerer, offer.price);
+      Self::_remove_offer(&offer);
+
+      Self::deposit_event(RawEvent::OfferCleared(offer_id));
+      Ok(())
+    } // end of fn `clear_expired_offer`
+
+    pub fn breed_kitty(origin, parent_a: T::Hash, parent_b: T::Hash, kitty_name: Vec<u8>) -> Result {
+      Self::_ensure_storage_migrated()?;
+      let sender = ensure_signed(origin)?;
+      // Check:
+      //  1. both parents exist and are different kitties
+      //  2. each parent is owned by the sender, approved to the sender for siring, or its
+      //     siring right is granted to the sender
+      //  3. both parents are not in auction
+      //  4. both parents are not in breeding cooldown
+
+      // check #1
+      ensure!(parent_a != parent_b, "A kitty cannot breed with itself");
+      ensure!(<Kitties<T>>::exists(parent_a) && <Kitties<T>>::exists(parent_b),
+        "Kitty does not exist");
+      let (kitty_a, kitty_b) = (Self::kitties(parent_a), Self::kitties(parent_b));
+      let now = <timestamp::Module<T>>::get();
+
+      for kitty in [&kitty_a, &kitty_b].iter() {
+        // check #2
+        ensure!(kitty.owner == Some(sender.clone()) ||
+          Self::siring_approval(kitty.id) == Some(sender.clone()) ||
+          Self::_siring_right_of(&kitty.id, &sender, &now),
+          "You are not allowed to breed with this kitty");
+
+        // check #3
+        ensure!(!kitty.in_auction, "Kitty is in an auction");
+
+        // check #4
+        ensure!(kitty.cooldown_end <= now, "Kitty is still in breeding cooldown");
+      }
+
+      // write:
+      //   1. mint the child kitty with the mixed DNA
+      //   2. record the child in the family tree of both parents
+      //   3. consume the siring approvals used, and start the parents cooldown
+      let dna = Self::_mix_dna(&kitty_a.dna, &kitty_b.dna, &Self::_gen_dna(&sender));
+      let generation = rstd::cmp::max(kitty_a.generation, kitty_b.generation) + 1;
+      // the breeder is the creator of the child, with no royalty on it
+      let kitty_id = Self::_mint_kitty(&sender, kitty_name, dna, Some((parent_a, parent_b)),
+        generation, Permill::zero())?;
+
+      for kitty in [&kitty_a, &kitty_b].iter() {
+        <KittyChildren<T>>::insert((kitty.id, Self::kitty_children_count(kitty.id)), kitty_id);
+        <KittyChildrenCount<T>>::mutate(kitty.id, |cnt| *cnt += 1);
+
+        if kitty.owner != Some(sender.clone()) {
+          if Self::_siring_right_of(&kitty.id, &sender, &now) {
+            <SiringRights<T>>::remove(kitty.id);
+          } else {
+            <SiringApprovals<T>>::remove(kitty.id);
+          }
+        }
+
+        let cooldown = Self::_breed_cooldown(kitty.breed_count);
+        <Kitties<T>>::mutate(kitty.id, |kitty| {
+          kitty.breed_count += 1;
+          kitty.cooldown_end = now.clone() + <T::Moment as As<u64>>::sa(cooldown);
+        });
+      }
+
+      Self::deposit_event(RawEvent::KittyBred(sender, kitty_id, parent_a, parent_b));
       Ok(())
+    } // end of fn `breed_kitty`
+
+    pub fn start_auction(origin, kitty_id: T::Hash, end_time: T::Moment,
+      base_price: T::Balance, pricing_rule: PricingRule,
+      soft_close: Option<SoftClose<T::Moment>>,
+      min_increment: Option<BidIncrement<T::Balance>>,
+      buy_now_price: Option<T::Balance>) -> Result {
+      Self::_ensure_storage_migrated()?;
+
+      let sender = ensure_signed(origin)?;
+      if let Some(ref soft_close) = soft_close {
+        let zero = <T::Moment as As<u64>>::sa(0);
+        ensure!(soft_close.window > zero && soft_close.extension > zero,
+          Error::SoftCloseZero.into());
+        ensure!(soft_close.max_end_time >= end_time, Error::SoftCloseBeforeEndTime.into());
+      }
+      if let Some(buy_now_price) = buy_now_price {
+        ensure!(buy_now_price >= base_price, Error::BuyNowBelowBasePrice.into());
+      }
+
+      let mut auction = Self::_new_auction(&sender, kitty_id, end_time, base_price, pricing_rule)?;
+      auction.soft_close = soft_close;
+      auction.min_increment = min_increment;
+      auction.buy_now_price = buy_now_price;
+
+      Self::_start_auction(sender, auction)
     } // end of `fn start_auction(...)
 
+    pub fn start_sealed_auction(origin, kitty_id: T::Hash, end_time: T::Moment,
+      reveal_end_time: T::Moment, base_price: T::Balance, deposit: T::Balance,
+      pricing_rule: PricingRule) -> Result {
+      Self::_ensure_storage_migrated()?;
+
+      let sender = ensure_signed(origin)?;
+      // Check:
+      //  1. the reveal window comes after the bidding window
+      //  2. deposit > 0, so there is something to forfeit when a bid is not revealed
+      ensure!(reveal_end_time > end_time, Error::RevealBeforeEndTime.into());
+      ensure!(deposit > <T::Balance as As<u64>>::sa(0),
+        Error::SealedDepositZero.into());
+
+      let mut auction = Self::_new_auction(&sender, kitty_id, end_time, base_price, pricing_rule)?;
+      auction.kind = AuctionKind::Sealed;
+      auction.reveal_end_time = reveal_end_time;
+      auction.sealed_deposit = deposit;
+
+      Self::_start_auction(sender, auction)
+    } // end of `fn start_sealed_auction(...)
+
+    pub fn start_dutch_auction(origin, kitty_id: T::Hash, end_time: T::Moment,
+      start_price: T::Balance, floor_price: T::Balance, step: T::Moment) -> Result {
+      Self::_ensure_storage_migrated()?;
+
+      let sender = ensure_signed(origin)?;
+      // Check:
+      //  1. the price falls from the start price to the floor price
+      ensure!(start_price > floor_price, Error::StartPriceNotAboveFloor.into());
+
+      let mut auction = Self::_new_auction(&sender, kitty_id, end_time, floor_price,
+        PricingRule::FirstPrice)?;
+      auction.kind = AuctionKind::Dutch;
+      auction.dutch_start_price = start_price;
+      auction.dutch_step = step;
+
+      Self::_start_auction(sender, auction)
+    } // end of `fn start_dutch_auction(...)
+
+    pub fn start_siring_auction(origin, kitty_id: T::Hash, end_time: T::Moment,
+      base_price: T::Balance, pricing_rule: PricingRule, right_duration: T::Moment) -> Result {
+      Self::_ensure_storage_migrated()?;
+
+      let sender = ensure_signed(origin)?;
+      // Check:
+      //  1. the siring right sold lasts for some time
+      ensure!(right_duration > <T::Moment as As<u64>>::sa(0),
+        Error::SiringDurationZero.into());
+
+      let mut auction = Self::_new_auction(&sender, kitty_id, end_time, base_price, pricing_rule)?;
+      auction.item = AuctionItem::SiringRight;
+      auction.siring_right_duration = right_duration;
+
+      Self::_start_auction(sender, auction)
+    } // end of `fn start_siring_auction(...)
+
     pub fn cancel_auction(origin, auction_id: T::Hash) -> Result {
+      Self::_ensure_storage_migrated()?;
 
       let sender = ensure_signed(origin)?;
 
@@ -231,30 +1216,38 @@ decl_module! {
       //   3. No one has placed bid in the auction yet
 
       // check #1:
-      ensure!(<Auctions<T>>::exists(auction_id), "Auction does not exist");
-      ensure!(Self::_auction_admin(auction_id) == sender, "You are not the auction admin");
+      ensure!(<Auctions<T>>::exists(auction_id), Error::AuctionNotExist.into());
+      ensure!(Self::_auction_admin(auction_id)? == sender, Error::NotAuctionAdmin.into());
 
       let auction = Self::auctions(auction_id);
       let kitty_id = auction.kitty_id;
       let now = <timestamp::Module<T>>::get();
       // check #2:
-      ensure!(auction.end_time > now, "The auction has passed its end time");
+      ensure!(auction.end_time > now, Error::AuctionEnded.into());
 
       // check #3:
-      ensure!(Self::auction_bids_count(auction_id) == 0,
-        "Someone has bidded already. So this auction cannot be cancelled");
+      ensure!(Self::auction_bids_count(auction_id) == 0, Error::AuctionHasBids.into());
 
       // write:
-      //   1. update the auction status to cancelled.
-      //   2. update the cat status
+      //   1. slash part of the listing deposit to the treasury, and return the rest
+      //   2. update the auction status to cancelled.
+      //   3. update the cat status
+      let slashed = Self::_slash_listing_deposit(&sender, auction.listing_deposit);
+      <balances::Module<T>>::unreserve(&sender, auction.listing_deposit - slashed);
+
       <Auctions<T>>::mutate(auction_id, |auction| auction.status = AuctionStatus::Cancelled);
+      Self::_ongoing_auctions_remove(&auction_id);
       <Kitties<T>>::mutate(kitty_id, |kitty| kitty.in_auction = false);
 
+      if slashed > <T::Balance as As<u64>>::sa(0) {
+        Self::deposit_event(RawEvent::ListingDepositSlashed(auction_id, slashed));
+      }
       Self::deposit_event(RawEvent::AuctionCancelled(auction_id));
       Ok(())
     } // end of `fn cancel_auction(...)`
 
     pub fn bid(origin, auction_id: T::Hash, bid_price: T::Balance) -> Result {
+      Self::_ensure_storage_migrated()?;
 
       let bidder = ensure_signed(origin)?;
       // check:
@@ -262,22 +1255,37 @@ decl_module! {
       //   2. bid_price >= base_price
       //   3. check the auction status is still ongoing
       //   4. now < auction end_time
+      //   5. bid_price beats the leading bid by the auction minimum increment, if set
 
       // check #1
-      ensure!(<Auctions<T>>::exists(auction_id), "Auction does not exist");
+      ensure!(<Auctions<T>>::exists(auction_id), Error::AuctionNotExist.into());
       let auction = Self::auctions(auction_id);
-      let kitty_owner = Self::kitties(auction.kitty_id).owner.ok_or("Kitty does not have owner")?;
-      ensure!(bidder != kitty_owner, "The kitty owner cannot bid in this auction");
+      let kitty_owner = Self::kitties(auction.kitty_id).owner.ok_or(Error::KittyNoOwner)?;
+      ensure!(bidder != kitty_owner, Error::OwnerCannotBid.into());
 
       // check #2
-      ensure!(bid_price >= auction.base_price, "The bid price is lower than the auction base price");
+      ensure!(bid_price >= auction.base_price, Error::BidBelowBasePrice.into());
 
       // check #3
-      ensure!(auction.status == AuctionStatus::Ongoing, "Auction is not active");
+      ensure!(auction.status == AuctionStatus::Ongoing, Error::AuctionNotActive.into());
+      ensure!(auction.kind == AuctionKind::English, Error::NotEnglishAuction.into());
 
       // check #4
       let now = <timestamp::Module<T>>::get();
-      ensure!(now < auction.end_time, "Auction has expired already");
+      ensure!(now < auction.end_time, Error::AuctionExpired.into());
+
+      // check #5
+      if let (Some(min_increment), Some(leading_bid_id)) =
+        (&auction.min_increment, auction.topmost_bids.get(0))
+      {
+        let leading_price = Self::bids(leading_bid_id).price;
+        let increment = match min_increment {
+          BidIncrement::Absolute(amount) => *amount,
+          BidIncrement::Percentage(percent) => *percent * leading_price,
+        };
+        let min_bid_price = leading_price.checked_add(&increment).ok_or(Error::BidIncrementOverflow)?;
+        ensure!(bid_price >= min_bid_price, Error::BidIncrementTooLow.into());
+      }
 
       //write #1
       let to_reserve: T::Balance;
@@ -287,7 +1295,7 @@ decl_module! {
 
         let bid = Self::bids(Self::auction_bidder_bids((auction_id, bidder.clone())));
         // check the current bid is larger than its previous bid
-        ensure!(bid_price > bid.price, "New bid has to be larger than your previous bid");
+        ensure!(bid_price > bid.price, Error::BidNotAbovePrevious.into());
 
         to_reserve = bid_price - bid.price;  // only reserve the difference from his previous bid
         <Bids<T>>::mutate(bid.id, |bid| {
@@ -306,11 +1314,13 @@ decl_module! {
           bidder: bidder.clone(),
           price: bid_price,
           last_update: now,
-          status: BidStatus::Active
+          status: BidStatus::Active,
+          commitment: None,
+          deposit: <T::Balance as As<u64>>::sa(0),
         };
 
         // check the bid ID is a new unique ID
-        ensure!(!<Bids<T>>::exists(&bid.id), "Generated bid ID is duplicated");
+        ensure!(!<Bids<T>>::exists(&bid.id), Error::BidIdExists.into());
 
         // add into storage
         <Bids<T>>::insert(bid.id, bid.clone());
@@ -318,132 +1328,470 @@ decl_module! {
           bid.id);
         <AuctionBidsCount<T>>::mutate(auction_id, |cnt| *cnt += 1);
         <AuctionBidderBids<T>>::insert((auction_id, bidder.clone()), bid.id);
+        Self::_bidder_bids_insert(&bidder, &bid.id);
         to_reserve = bid_price;
 
         bid  // bid returned
       };
 
       // bidder money has to be locked here
-      <balances::Module<T>>::reserve(&bidder, to_reserve)?;
+      <balances::Module<T>>::reserve(&bidder, to_reserve).map_err(|_| Error::BidUnaffordable)?;
 
-      // update auction bid info inside if higher than topmost
-      if bid_price >= auction.price_to_topmost {
-        let _ = Self::_update_auction_topmost_bids(&auction_id, &bid.id);
-      }
+      // update auction bid info inside if it makes into the topmost bids
+      let _ = Self::_update_auction_topmost_bids(&auction_id, &bid.id);
 
       // emit an event
       Self::deposit_event(RawEvent::NewBid(auction_id, bid_price));
 
+      // a bid in the soft close window extends the auction. The expiry queue picks up
+      //   the new end time when it visits the auction.
+      if let Some(soft_close) = auction.soft_close {
+        if now.clone() + soft_close.window >= auction.end_time {
+          let extended = auction.end_time.clone() + soft_close.extension;
+          let new_end_time = if extended < soft_close.max_end_time { extended }
+            else { soft_close.max_end_time };
+
+          if new_end_time > auction.end_time {
+            <Auctions<T>>::mutate(auction_id, |auction| auction.end_time = new_end_time.clone());
+            Self::deposit_event(RawEvent::AuctionExtended(auction_id, new_end_time));
+          }
+        }
+      }
+
       Ok(())
     }
 
-    pub fn update_auction_display_bids(_origin, auction_id: T::Hash) -> Result {
-      // no need to verify caller, anyone can call this method
+    pub fn buy_now(origin, auction_id: T::Hash) -> Result {
+      Self::_ensure_storage_migrated()?;
 
+      let buyer = ensure_signed(origin)?;
       // check:
-      //   1. auction existed
-      //   2. auction is still ongoing
-      //   3. its last updated time passed the DISPLAY_BIDS_UPDATE_PERIOD
-      ensure!(<Auctions<T>>::exists(auction_id), "The auction does not exist");
-      let now = <timestamp::Module<T>>::get();
+      //   1. the auction is ongoing and has a buy-it-now price, not reached by the leading bid
+      //   2. buyer is not the kitty owner
+      //   3. now < auction end_time
+      //   4. buyer can afford the price, counting in his own bid in the auction
+
+      // check #1
+      ensure!(<Auctions<T>>::exists(auction_id), Error::AuctionNotExist.into());
       let auction = Self::auctions(auction_id);
-      let to_update = DISPLAY_BIDS_UPDATE_PERIOD + auction.display_bids_last_update.as_();
+      ensure!(auction.status == AuctionStatus::Ongoing, Error::AuctionNotActive.into());
+      ensure!(auction.kind == AuctionKind::English, Error::BuyNowNotEnglish.into());
+      let price = auction.buy_now_price.ok_or(Error::NoBuyNowPrice)?;
+      // once bidders offer the buy-it-now price, the kitty goes to the best of them
+      if let Some(leading_bid_id) = auction.topmost_bids.get(0) {
+        ensure!(Self::bids(leading_bid_id).price < price, Error::BuyNowOutbid.into());
+      }
 
-      ensure!(auction.status == AuctionStatus::Ongoing, "The auction is no longer running.");
-      ensure!(to_update <= now.clone().as_(), "The auction display bids has just been recently updated.");
+      // check #2
+      ensure!(buyer != Self::_auction_admin(auction_id)?, Error::OwnerCannotBuy.into());
 
-      Self::_update_auction_display_bids_nocheck(auction_id, true)
+      // check #3
+      let now = <timestamp::Module<T>>::get();
+      ensure!(now < auction.end_time, Error::AuctionExpired.into());
+
+      // check #4
+      let mut affordable = <balances::Module<T>>::free_balance(&buyer);
+      if <AuctionBidderBids<T>>::exists((auction_id, buyer.clone())) {
+        affordable = affordable + Self::bids(Self::auction_bidder_bids((auction_id, buyer.clone()))).price;
+      }
+      ensure!(affordable >= price, Error::BuyUnaffordable.into());
+
+      Self::_settle_auction(auction_id, Some((buyer, price)))
     }
 
-    pub fn close_auction_and_tx(_origin, auction_id: T::Hash) -> Result {
-      ensure!(<Auctions<T>>::exists(auction_id), "The auction does not exist");
+    pub fn accept_price(origin, auction_id: T::Hash) -> Result {
+      Self::_ensure_storage_migrated()?;
+
+      let buyer = ensure_signed(origin)?;
+      // check:
+      //   1. it is an ongoing dutch auction
+      //   2. buyer is not the kitty owner
+      //   3. now < auction end_time
+      //   4. buyer can afford the current price
+
+      // check #1
+      ensure!(<Auctions<T>>::exists(auction_id), Error::AuctionNotExist.into());
+      let auction = Self::auctions(auction_id);
+      ensure!(auction.status == AuctionStatus::Ongoing, Error::AuctionNotActive.into());
+      ensure!(auction.kind == AuctionKind::Dutch, Error::NotDutchAuction.into());
+
+      // check #2
+      ensure!(buyer != Self::_auction_admin(auction_id)?, Error::OwnerCannotBuy.into());
+
+      // check #3
       let now = <timestamp::Module<T>>::get();
+      ensure!(now < auction.end_time, Error::AuctionExpired.into());
+
+      // check #4
+      let price = Self::_dutch_price(&auction, now);
+      ensure!(<balances::Module<T>>::free_balance(&buyer) >= price, Error::BuyUnaffordable.into());
+
+      Self::_settle_auction(auction_id, Some((buyer, price)))
+    }
+
+    pub fn commit_bid(origin, auction_id: T::Hash, commitment: T::Hash) -> Result {
+      Self::_ensure_storage_migrated()?;
+
+      let bidder = ensure_signed(origin)?;
+      // check:
+      //   1. bidder is not the kitty owner
+      //   2. it is an ongoing sealed-bid auction
+      //   3. now < auction end_time
+
+      // check #1
+      ensure!(<Auctions<T>>::exists(auction_id), Error::AuctionNotExist.into());
       let auction = Self::auctions(auction_id);
+      let kitty_owner = Self::kitties(auction.kitty_id).owner.ok_or(Error::KittyNoOwner)?;
+      ensure!(bidder != kitty_owner, Error::OwnerCannotBid.into());
 
-      ensure!(auction.status == AuctionStatus::Ongoing, "The auction is no longer running.");
-      ensure!(now >= auction.end_time, "The auction is not expired yet.");
-
-      // write
-      //   1. check if there is a highest bidder. If yes
-      //     - unreserve his money,
-      //     - transfer his money to kitty_owner
-      //     - update kitty to the bidder
-      //     - emit an event saying an auction with aid has a transaction, of kitty_id
-      //       from AccountId to AccountId
-      //   2. unreserve all fund from the rest of the bidders
-      //   3. set auction status to Closed
-      //     - emit an event saying auction closed
-
-      // #1. Transact the kitty and money between winner and kitty owner
-      let mut winner_opt: Option<T::AccountId> = None;
-      let mut auction_tx_opt: Option<AuctionTx<T::Moment, T::AccountId, T::Balance>> = None;
-
-      if auction.topmost_bids.len() > 0 {
-        let reward_bid = Self::bids(auction.topmost_bids[0]);
-        winner_opt = Some(reward_bid.bidder.clone());
-        let kitty_owner = Self::kitties(auction.kitty_id).owner.unwrap();
-
-        // 1) unreserve winner money,
-        // 2) transfer winner money to kitty_owner,
-        // 3) transfer kitty ownership to the winner
-        if let Some(ref winner_ref) = winner_opt {
-          <balances::Module<T>>::unreserve(winner_ref, reward_bid.price);
-          let _transfer = <balances::Module<T> as Currency<_>>::transfer(winner_ref, &kitty_owner, reward_bid.price);
-          match _transfer {
-            Err(_e) => Err("Fund transfer error"),
-            Ok(_v) => {
-              Self::_transfer_kitty_ownership(&auction.kitty_id, winner_ref);
-
-              // create the auction_tx here
-              auction_tx_opt = Some(AuctionTx {
-                tx_time: now,
-                winner: winner_ref.clone(),
-                tx_price: reward_bid.price
-              });
-
-              // emit event of the kitty is transferred
-              Self::deposit_event(RawEvent::AuctionTx(auction_id, auction.kitty_id, kitty_owner, winner_opt.clone().unwrap()));
-              Ok(())
-            },
-          }?;
-        }
-      } else {
-        // No one bid. So no kitty ownership transfer is made. Resume the kitty to the owner
-        <Kitties<T>>::mutate(Self::auctions(auction_id).kitty_id, |kitty| {
-          kitty.in_auction = false;
-        });
-      }
+      // check #2
+      ensure!(auction.status == AuctionStatus::Ongoing, Error::AuctionNotActive.into());
+      ensure!(auction.kind == AuctionKind::Sealed, Error::NotSealedAuction.into());
 
-      // #2. unreserve funds for other bidders
-      let bids_count = <AuctionBidsCount<T>>::get(auction_id);
-      (0..bids_count)
-        .map(|i| Self::bids( Self::auction_bids((auction_id, i)) ) )  // get the bids
-        .filter(|bid| match &winner_opt {                             // filter out the auction winner
-          Some(winner) => *winner != bid.bidder,
-          None => true
-        })
-        .for_each(|bid| {                                             // unreserve funds for other bidders
-          <balances::Module<T>>::unreserve(&bid.bidder, bid.price);
+      // check #3
+      let now = <timestamp::Module<T>>::get();
+      ensure!(now < auction.end_time, Error::AuctionExpired.into());
+
+      // write:
+      //   - when the bidder has committed already, replace his commitment. The deposit
+      //     is reserved already.
+      //   - otherwise, create a new bid and reserve the deposit.
+      let bid_id = if <AuctionBidderBids<T>>::exists((auction_id, bidder.clone())) {
+        let bid_id = Self::auction_bidder_bids((auction_id, bidder.clone()));
+        <Bids<T>>::mutate(bid_id, |bid| {
+          bid.commitment = Some(commitment);
+          bid.last_update = now;
         });
 
-      // #3. close the auction and emit event
-      <Auctions<T>>::mutate(auction_id, |auction| {
-        auction.status = AuctionStatus::Closed;
-        auction.tx = auction_tx_opt;
+        bid_id // bid_id returned
+      } else {
+        let bid = Bid {
+          id: Self::_gen_random_hash(&bidder)?,
+          auction_id,
+          bidder: bidder.clone(),
+          price: <T::Balance as As<u64>>::sa(0),
+          last_update: now,
+          status: BidStatus::Committed,
+          commitment: Some(commitment),
+          deposit: auction.sealed_deposit,
+        };
+
+        // check the bid ID is a new unique ID
+        ensure!(!<Bids<T>>::exists(&bid.id), Error::BidIdExists.into());
+
+        // the deposit has to be locked before the bid is stored
+        <balances::Module<T>>::reserve(&bidder, bid.deposit)
+          .map_err(|_| Error::BidDepositUnaffordable)?;
+
+        <Bids<T>>::insert(bid.id, bid.clone());
+        <AuctionBids<T>>::insert((auction_id, Self::auction_bids_count(auction_id)),
+          bid.id);
+        <AuctionBidsCount<T>>::mutate(auction_id, |cnt| *cnt += 1);
+        <AuctionBidderBids<T>>::insert((auction_id, bidder.clone()), bid.id);
+        Self::_bidder_bids_insert(&bidder, &bid.id);
+
+        bid.id // bid_id returned
+      };
+
+      Self::deposit_event(RawEvent::BidCommitted(auction_id, bid_id));
+      Ok(())
+    }
+
+    pub fn reveal_bid(origin, auction_id: T::Hash, bid_price: T::Balance, salt: T::Hash) -> Result {
+      Self::_ensure_storage_migrated()?;
+
+      let bidder = ensure_signed(origin)?;
+      // check:
+      //   1. it is a sealed-bid auction in its reveal window
+      //   2. the bidder has a committed bid not revealed yet
+      //   3. the revealed price and salt match the commitment
+      //   4. bid_price >= base_price
+
+      // check #1
+      ensure!(<Auctions<T>>::exists(auction_id), Error::AuctionNotExist.into());
+      let auction = Self::auctions(auction_id);
+      ensure!(auction.status == AuctionStatus::Ongoing, Error::AuctionNotActive.into());
+      ensure!(auction.kind == AuctionKind::Sealed, Error::NotSealedAuction.into());
+      let now = <timestamp::Module<T>>::get();
+      ensure!(now >= auction.end_time, Error::RevealTooEarly.into());
+      ensure!(now < auction.reveal_end_time, Error::RevealPeriodOver.into());
+
+      // check #2
+      ensure!(<AuctionBidderBids<T>>::exists((auction_id, bidder.clone())),
+        Error::NoBidInAuction.into());
+      let bid = Self::bids(Self::auction_bidder_bids((auction_id, bidder.clone())));
+      ensure!(bid.status == BidStatus::Committed, Error::BidRevealed.into());
+
+      // check #3
+      ensure!(bid.commitment == Some(Self::bid_commitment(auction_id, &bidder, bid_price, salt)),
+        Error::CommitmentMismatch.into());
+
+      // check #4
+      ensure!(bid_price >= auction.base_price, Error::BidBelowBasePrice.into());
+
+      // write:
+      //   1. lock the bid price
+      //   2. the bid becomes active
+      //   3. update auction topmost bids
+      <balances::Module<T>>::reserve(&bidder, bid_price).map_err(|_| Error::BidUnaffordable)?;
+
+      <Bids<T>>::mutate(bid.id, |bid| {
+        bid.price = bid_price;
+        bid.status = BidStatus::Active;
+        bid.last_update = now;
       });
 
-      // #4. update the display bid upon closing
-      let _ = Self::_update_auction_display_bids_nocheck(auction_id, false);
+      let _ = Self::_update_auction_topmost_bids(&auction_id, &bid.id);
+
+      Self::deposit_event(RawEvent::BidRevealed(auction_id, bid.id, bid_price));
+      Ok(())
+    }
+
+    pub fn update_auction_display_bids(_origin, auction_id: T::Hash) -> Result {
+      Self::_ensure_storage_migrated()?;
+      // no need to verify caller, anyone can call this method
+
+      // check:
+      //   1. auction existed
+      //   2. auction is still ongoing
+      //   3. its last updated time passed the display bids update period
+      ensure!(<Auctions<T>>::exists(auction_id), Error::AuctionNotExist.into());
+      let now = <timestamp::Module<T>>::get();
+      let auction = Self::auctions(auction_id);
+      let to_update = Self::display_bids_update_period() + auction.display_bids_last_update.as_();
+
+      ensure!(auction.status == AuctionStatus::Ongoing, Error::AuctionNotActive.into());
+      ensure!(to_update <= now.clone().as_(), Error::DisplayBidsRecentlyUpdated.into());
+
+      Self::_update_auction_display_bids_nocheck(auction_id, true)
+    }
+
+    pub fn withdraw_bid(origin, auction_id: T::Hash) -> Result {
+      Self::_ensure_storage_migrated()?;
+
+      let bidder = ensure_signed(origin)?;
+      // check:
+      //   1. the auction exists and is still ongoing
+      //   2. the bidder has an active bid in the auction
+      //   3. the bid is not among the last displayed bids. Those cannot be withdrawn
+
+      // check #1
+      ensure!(<Auctions<T>>::exists(auction_id), Error::AuctionNotExist.into());
+      let auction = Self::auctions(auction_id);
+      ensure!(auction.status == AuctionStatus::Ongoing, Error::AuctionNotActive.into());
+      ensure!(auction.kind == AuctionKind::English, Error::WithdrawNotEnglish.into());
+
+      // check #2
+      ensure!(<AuctionBidderBids<T>>::exists((auction_id, bidder.clone())),
+        Error::NoBidInAuction.into());
+      let bid = Self::bids(Self::auction_bidder_bids((auction_id, bidder.clone())));
+      ensure!(bid.status == BidStatus::Active, Error::BidNotActive.into());
+
+      // check #3
+      ensure!(!auction.display_bids.contains(&bid.id),
+        Error::DisplayedBidWithdrawal.into());
+
+      // write:
+      //   1. return the bidder money
+      //   2. mark the bid withdrawn, and remove it from the bidder index
+      //   3. the bid could be in the topmost bids already, if so rebuild them
+      <balances::Module<T>>::unreserve(&bidder, bid.price);
+
+      <Bids<T>>::mutate(bid.id, |bid| bid.status = BidStatus::Withdrawn);
+      <AuctionBidderBids<T>>::remove((auction_id, bidder.clone()));
+      Self::_bidder_bids_remove(&bidder, &bid.id);
+
+      if auction.topmost_bids.contains(&bid.id) {
+        Self::_rebuild_auction_topmost_bids(&auction_id)?;
+      }
+
+      Self::deposit_event(RawEvent::BidWithdrawn(auction_id, bid.id, bidder));
+      Ok(())
+    }
+
+    pub fn set_protocol_fee(origin, fee: Permill) -> Result {
+      Self::_ensure_storage_migrated()?;
+      ensure_root(origin)?;
+
+      <ProtocolFee<T>>::put(fee);
+
+      Self::deposit_event(RawEvent::ProtocolFeeSet(fee));
+      Ok(())
+    }
+
+    pub fn set_listing_deposit(origin, deposit: T::Balance) -> Result {
+      Self::_ensure_storage_migrated()?;
+      ensure_root(origin)?;
+
+      <ListingDeposit<T>>::put(deposit);
+
+      Self::deposit_event(RawEvent::ListingDepositSet(deposit));
+      Ok(())
+    }
+
+    // Topmost bids of ongoing auctions are truncated or refilled on their next update
+    pub fn set_topmost_bids_len(origin, len: u32) -> Result {
+      Self::_ensure_storage_migrated()?;
+      ensure_root(origin)?;
+      ensure!(len > 0 && len <= MAX_TOPMOST_BIDS_LEN, Error::TopmostBidsLenOutOfRange.into());
+
+      <TopmostBidsLen<T>>::put(len);
+
+      Self::deposit_event(RawEvent::TopmostBidsLenSet(len));
+      Ok(())
+    }
+
+    // Only auctions started afterwards are checked against the new duration
+    pub fn set_auction_min_duration(origin, duration: u64) -> Result {
+      Self::_ensure_storage_migrated()?;
+      ensure_root(origin)?;
+      ensure!(duration >= MIN_AUCTION_MIN_DURATION && duration <= MAX_AUCTION_MIN_DURATION,
+        Error::AuctionMinDurationOutOfRange.into());
+
+      <AuctionMinDuration<T>>::put(duration);
+
+      Self::deposit_event(RawEvent::AuctionMinDurationSet(duration));
+      Ok(())
+    }
+
+    // Refreshes already queued keep their time, and the refreshes after them follow the new period
+    pub fn set_display_bids_update_period(origin, period: u64) -> Result {
+      Self::_ensure_storage_migrated()?;
+      ensure_root(origin)?;
+      ensure!(period > 0 && period <= MAX_DISPLAY_BIDS_UPDATE_PERIOD,
+        Error::DisplayPeriodOutOfRange.into());
 
-      Self::deposit_event(RawEvent::AuctionClosed(auction_id));
+      <DisplayBidsUpdatePeriod<T>>::put(period);
 
+      Self::deposit_event(RawEvent::DisplayBidsUpdatePeriodSet(period));
       Ok(())
     }
 
+    pub fn close_auction_and_tx(_origin, auction_id: T::Hash) -> Result {
+      Self::_ensure_storage_migrated()?;
+      ensure!(<Auctions<T>>::exists(auction_id), Error::AuctionNotExist.into());
+      let now = <timestamp::Module<T>>::get();
+      let auction = Self::auctions(auction_id);
+
+      ensure!(auction.status == AuctionStatus::Ongoing, Error::AuctionNotActive.into());
+      ensure!(now >= Self::_auction_close_time(&auction), Error::AuctionNotExpired.into());
+
+      Self::_close_auction(auction_id)
+    }
+
   } // end of `struct Module<T: Trait> for enum Call...`
 } // end of `decl_module!`
 
 impl<T: Trait> Module<T> {
+  // the commitment a sealed bid submits in `commit_bid`, and is checked against in `reveal_bid`.
+  //   It binds the auction and the bidder, so a copied commitment cannot be revealed by another bidder
+  pub fn bid_commitment(auction_id: T::Hash, bidder: &T::AccountId, bid_price: T::Balance,
+    salt: T::Hash) -> T::Hash
+  {
+    (auction_id, bidder, bid_price, salt).using_encoded(<T as system::Trait>::Hashing::hash)
+  }
+
+  // the account collecting protocol fees. No one holds its key
+  pub fn treasury_account() -> T::AccountId {
+    let seed_hash = <T as system::Trait>::Hashing::hash(TREASURY_SEED);
+    T::AccountId::decode(&mut seed_hash.as_ref()).unwrap_or_default()
+  }
+
+  // Queries served by the runtime API. Pages hold `MAX_QUERY_PAGE_LEN` items at most
+
+  pub fn kitties_of(owner: &T::AccountId, offset: u64, limit: u64) -> Vec<KittyOf<T>> {
+    Self::owned_kitty_ids(owner).into_iter()
+      .skip(offset as usize)
+      .take(rstd::cmp::min(limit, MAX_QUERY_PAGE_LEN) as usize)
+      .map(|kitty_id| Self::kitties(kitty_id))
+      .collect()
+  }
+
+  pub fn find_kitty(kitty_id: T::Hash) -> Option<KittyOf<T>> {
+    if !<Kitties<T>>::exists(kitty_id) { return None; }
+    Some(Self::kitties(kitty_id))
+  }
+
+  pub fn ongoing_auctions(offset: u64, limit: u64) -> Vec<AuctionOf<T>> {
+    (offset..Self::_page_end(offset, limit, Self::ongoing_auctions_count()))
+      .map(|i| Self::auctions(Self::ongoing_auction(i)))
+      .collect()
+  }
+
+  // the auction with its kitty and displayed bids. The rest of the bids stay undisclosed
+  pub fn auction_detail(auction_id: T::Hash) -> Option<AuctionDetailOf<T>> {
+    if !<Auctions<T>>::exists(auction_id) { return None; }
+
+    let auction = Self::auctions(auction_id);
+    Some(AuctionDetail {
+      kitty: Self::kitties(auction.kitty_id),
+      display_bids: auction.display_bids.iter().map(|bid_id| Self::bids(bid_id)).collect(),
+      bids_count: Self::auction_bids_count(auction_id),
+      auction,
+    })
+  }
+
+  // bids of the account not withdrawn, in the order they are placed. Withdrawing a bid moves
+  //   the last bid of the account to its place
+  pub fn bids_of(account: &T::AccountId, offset: u64, limit: u64) -> Vec<BidOf<T>> {
+    (offset..Self::_page_end(offset, limit, Self::bidder_bids_count(account)))
+      .map(|i| Self::bids(Self::bidder_bid((account.clone(), i))))
+      .collect()
+  }
+
+  // the end of the page from `offset` in an index of `count` items
+  fn _page_end(offset: u64, limit: u64, count: u64) -> u64 {
+    rstd::cmp::min(offset.saturating_add(rstd::cmp::min(limit, MAX_QUERY_PAGE_LEN)), count)
+  }
+
+  fn _ongoing_auctions_insert(auction_id: &T::Hash) {
+    let count = Self::ongoing_auctions_count();
+    <OngoingAuctions<T>>::insert(count, auction_id);
+    <OngoingAuctionsIndex<T>>::insert(auction_id, count);
+    <OngoingAuctionsCount<T>>::put(count + 1);
+  }
+
+  // remove the auction from the ongoing auctions, moving the last one to its place
+  fn _ongoing_auctions_remove(auction_id: &T::Hash) {
+    let index = match Self::ongoing_auctions_index(auction_id) {
+      Some(index) => index,
+      None => return,
+    };
+    let last = Self::ongoing_auctions_count() - 1;
+    if index != last {
+      let last_auction_id = Self::ongoing_auction(last);
+      <OngoingAuctions<T>>::insert(index, last_auction_id);
+      <OngoingAuctionsIndex<T>>::insert(last_auction_id, index);
+    }
+    <OngoingAuctions<T>>::remove(last);
+    <OngoingAuctionsIndex<T>>::remove(auction_id);
+    <OngoingAuctionsCount<T>>::put(last);
+  }
+
+  fn _bidder_bids_insert(bidder: &T::AccountId, bid_id: &T::Hash) {
+    let count = Self::bidder_bids_count(bidder);
+    <BidderBids<T>>::insert((bidder.clone(), count), bid_id);
+    <BidderBidsIndex<T>>::insert(bid_id, count);
+    <BidderBidsCount<T>>::insert(bidder, count + 1);
+  }
+
+  // remove the bid from the bids of the bidder, moving the last one to its place
+  fn _bidder_bids_remove(bidder: &T::AccountId, bid_id: &T::Hash) {
+    let index = match Self::bidder_bids_index(bid_id) {
+      Some(index) => index,
+      None => return,
+    };
+    let last = Self::bidder_bids_count(bidder) - 1;
+    if index != last {
+      let last_bid_id = Self::bidder_bid((bidder.clone(), last));
+      <BidderBids<T>>::insert((bidder.clone(), index), last_bid_id);
+      <BidderBidsIndex<T>>::insert(last_bid_id, index);
+    }
+    <BidderBids<T>>::remove((bidder.clone(), last));
+    <BidderBidsIndex<T>>::remove(bid_id);
+    <BidderBidsCount<T>>::insert(bidder, last);
+  }
+
   // generate a random hash key
   fn _gen_random_hash(sender: &T::AccountId) -> StdResult<T::Hash> {
     let nonce = <Nonce<T>>::get();
@@ -456,8 +1804,93 @@ impl<T: Trait> Module<T> {
     Ok(random_hash)
   }
 
+  // entropy for the DNA of a new kitty. It is mixed from other inputs than the kitty ID and
+  //   the module nonce, under its own domain tag, so it does not follow from the ID. The
+  //   kitties count sets apart kitties minted in one extrinsic. No caller-chosen input goes
+  //   in, so it cannot be retried offline for rare traits. It is not secure randomness
+  //   though: every input is known once the parent block is, so a caller can predict the
+  //   DNA for a given extrinsic index before submitting, and the block author, choosing the
+  //   extrinsic order, can grind it
+  fn _gen_dna(sender: &T::AccountId) -> T::Hash {
+    (
+      b"cat_auction/dna",
+      <system::Module<T>>::random_seed(),
+      <system::Module<T>>::parent_hash(),
+      <system::Module<T>>::block_number(),
+      <system::Module<T>>::extrinsic_index(),
+      Self::kitties_count(),
+      sender,
+    ).using_encoded(<T as system::Trait>::Hashing::hash)
+  }
+
+  // create a new kitty owned by `owner` and store it
+  fn _mint_kitty(owner: &T::AccountId, kitty_name: Vec<u8>, dna: T::Hash,
+    parents: Option<(T::Hash, T::Hash)>, generation: u64, royalty: Permill) -> StdResult<T::Hash>
+  {
+    let kitty_id = Self::_gen_random_hash(owner)?;
+    // ensure the kitty_id is not existed
+    ensure!(!<Kitties<T>>::exists(&kitty_id), "Cat with the id existed already");
+
+    let mut kitty = Kitty {
+      id: kitty_id,
+      name: Some(kitty_name.clone()),
+      owner: None,      // to be updated in _add_kitty_to_storage
+      in_auction: false,
+      dna,
+      traits: KittyTraits::from_dna(dna.as_ref()),
+      parents,
+      generation,
+      breed_count: 0,
+      cooldown_end: Default::default(),
+      creator: Some(owner.clone()),
+      royalty,
+    };
+    Self::_add_kitty_to_storage(&mut kitty, Some(owner))?;
+
+    // emit an event
+    Self::deposit_event(RawEvent::KittyCreated(owner.clone(), kitty_id, kitty_name));
+    Ok(kitty_id)
+  }
+
+  // whether the kitty has a siring right sold that is not used and not expired yet
+  fn _siring_locked(kitty_id: &T::Hash, now: &T::Moment) -> bool {
+    match Self::siring_right(kitty_id) {
+      Some(right) => right.expires > *now,
+      None => false,
+    }
+  }
+
+  // whether `account` holds a valid siring right of the kitty
+  fn _siring_right_of(kitty_id: &T::Hash, account: &T::AccountId, now: &T::Moment) -> bool {
+    match Self::siring_right(kitty_id) {
+      Some(right) => right.grantee == *account && right.expires > *now,
+      None => false,
+    }
+  }
+
+  // child DNA takes each byte from either parent at random, with a small chance of mutation
+  fn _mix_dna(dna_a: &T::Hash, dna_b: &T::Hash, random: &T::Hash) -> T::Hash {
+    let (dna_a, dna_b, random) = (dna_a.as_ref(), dna_b.as_ref(), random.as_ref());
+    let mut dna = T::Hash::default();
+
+    for (i, gene) in dna.as_mut().iter_mut().enumerate() {
+      *gene = if random[i] & 1 == 0 { dna_a[i] } else { dna_b[i] };
+      // about 1 in 32 genes mutates
+      if random[i] < 8 {
+        *gene ^= random[(i + 1) % random.len()];
+      }
+    }
+    dna
+  }
+
+  // cooldown in seconds after a kitty has bred `breed_count` times before
+  fn _breed_cooldown(breed_count: u32) -> u64 {
+    let factor = 1u64 << rstd::cmp::min(breed_count, 32);
+    rstd::cmp::min(BREED_COOLDOWN_BASE.saturating_mul(factor), BREED_COOLDOWN_MAX)
+  }
+
   // allow owner to be None
-  fn _add_kitty_to_storage(kitty: &mut Kitty<T::Hash, T::AccountId>, owner: Option<&T::AccountId>)
+  fn _add_kitty_to_storage(kitty: &mut KittyOf<T>, owner: Option<&T::AccountId>)
     -> Result
   {
     let kitty_id: T::Hash = kitty.id;
@@ -465,10 +1898,9 @@ impl<T: Trait> Module<T> {
     // add the owner reference if `owner` is specified
     if let Some(owner_id) = owner {
       kitty.owner = Some(owner_id.clone());
-      kitty.owner_pos = Some(Self::owner_kitties_count(owner_id));
 
-      // update OwnerKitties storage...
-      <OwnerKitties<T>>::insert((owner_id.clone(), kitty.owner_pos.unwrap()), &kitty_id);
+      // update OwnedKitties storage...
+      Self::_owned_kitties_append(owner_id, &kitty_id);
       <OwnerKittiesCount<T>>::mutate(owner_id, |cnt| *cnt += 1);
     }
 
@@ -480,119 +1912,864 @@ impl<T: Trait> Module<T> {
     Ok(())
   }
 
-  fn _add_auction_to_storage(auction: &Auction<T::Hash, T::Balance,
-    T::Moment, AuctionTx<T::Moment, T::AccountId, T::Balance>>) -> Result
+  // check the kitty can be put in auction, and create an english auction object of it.
+  //   The auction is not stored yet.
+  fn _new_auction(sender: &T::AccountId, kitty_id: T::Hash, end_time: T::Moment,
+    base_price: T::Balance, pricing_rule: PricingRule) -> StdResult<AuctionOf<T>>
   {
+    // Check:
+    //  1. ensure kitty exists, and the kitty.owner == sender. Currently,
+    //     only the kitty owner can put his own kitty in auction
+    //  2. kitty is not already `in_auction` state, nor locked by a siring right, nor listed
+    //     at a fixed price
+    //  3. ensure end_time > current_time
+    //  4. base_price > 0
+
+    // check #1
+    ensure!(<Kitties<T>>::exists(kitty_id), Error::KittyNotExist.into());
+    let kitty = Self::kitties(kitty_id);
+    ensure!(kitty.owner == Some(sender.clone()), Error::NotKittyOwner.into());
+
+    // check #2
+    let now = <timestamp::Module<T>>::get();
+    ensure!(!kitty.in_auction, Error::KittyInAuction.into());
+    ensure!(!Self::_siring_locked(&kitty_id, &now), Error::KittySiringLocked.into());
+    ensure!(Self::kitty_price(kitty_id).is_none(), Error::KittyListed.into());
+
+    // check #3
+    ensure!(end_time.clone().as_() > Self::auction_min_duration() + now.clone().as_(),
+      Error::EndTimeTooSoon.into());
+
+    // check #4
+    ensure!(base_price > <T::Balance as As<u64>>::sa(0), Error::BasePriceZero.into());
+
+    // create the auction
+    let auction_id = Self::_gen_random_hash(sender)?;
+    // check: auction_id not existed yet
+    ensure!(!<Auctions<T>>::exists(&auction_id), Error::AuctionIdExists.into());
+
+    let auction = Auction {
+      id: auction_id.clone(),
+      kitty_id,
+      base_price,
+      start_time: now.clone(),
+      end_time: end_time.clone(),
+      status: AuctionStatus::Ongoing,
+      pricing_rule,
+      kind: AuctionKind::English,
+      reveal_end_time: Default::default(),
+      sealed_deposit: <T::Balance as As<u64>>::sa(0),
+      listing_deposit: <T::Balance as As<u64>>::sa(0),
+      soft_close: None,
+      min_increment: None,
+      buy_now_price: None,
+      dutch_start_price: <T::Balance as As<u64>>::sa(0),
+      dutch_step: Default::default(),
+      item: AuctionItem::Ownership,
+      siring_right_duration: Default::default(),
+
+      topmost_bids: Vec::new(),
+      price_to_topmost: base_price,
+      display_bids: Vec::new(),
+      display_bids_last_update: now,
+
+      tx: None,
+    };
+    Ok(auction)
+  }
+
+  fn _start_auction(sender: T::AccountId, mut auction: AuctionOf<T>) -> Result {
+    // the listing deposit has to be locked before the auction is stored
+    let listing_deposit = Self::listing_deposit();
+    <balances::Module<T>>::reserve(&sender, listing_deposit)
+      .map_err(|_| Error::ListingDepositUnaffordable)?;
+    auction.listing_deposit = listing_deposit;
+
+    Self::_add_auction_to_storage(&auction)?;
+
+    // also set the kitty state in_auction = true
+    <Kitties<T>>::mutate(auction.kitty_id, |k| k.in_auction = true);
+
+    // emit an event
+    Self::deposit_event(RawEvent::AuctionStarted(sender, auction.kitty_id, auction.id,
+      auction.base_price, auction.end_time));
+    Ok(())
+  }
+
+  fn _add_auction_to_storage(auction: &AuctionOf<T>) -> Result {
     <Auctions<T>>::insert(auction.id, auction);
     <AuctionsArray<T>>::insert(Self::auctions_count(), auction.id);
     <AuctionsCount<T>>::mutate(|cnt| *cnt += 1);
+    Self::_ongoing_auctions_insert(&auction.id);
 
+    Self::_schedule_auction(auction);
     Ok(())
   }
 
-  fn _auction_admin(auction_id: T::Hash) -> T::AccountId {
-    // we use an internal function here, so later on we can modify the logic
-    //   how an auction admin is determined.
+  // queue the auction for automatic settlement and display bids refresh
+  fn _schedule_auction(auction: &AuctionOf<T>) {
+    Self::_enqueue_auction_expiry(auction.id, Self::_auction_close_time(auction));
+    let next_refresh = auction.display_bids_last_update +
+      <T::Moment as As<u64>>::sa(Self::display_bids_update_period());
+    <NextDisplayRefresh<T>>::insert(auction.id, next_refresh.clone());
+    Self::_enqueue_display_refresh(auction.id, next_refresh);
+  }
 
-    let auction = Self::auctions(auction_id);
-    let kitty = Self::kitties(auction.kitty_id);
-    kitty.owner.unwrap()
+  // the price the winner pays, depending on the auction pricing rule. Only meaningful
+  //   when the auction has at least one topmost bid.
+  fn _auction_tx_price(auction: &AuctionOf<T>) -> T::Balance {
+    match auction.pricing_rule {
+      PricingRule::FirstPrice => Self::bids(auction.topmost_bids[0]).price,
+      PricingRule::SecondPrice => match auction.topmost_bids.get(1) {
+        Some(second_bid_id) => {
+          let second_price = Self::bids(second_bid_id).price;
+          if second_price > auction.base_price { second_price } else { auction.base_price }
+        },
+        None => auction.base_price,
+      },
+    }
   }
 
-  fn _update_auction_topmost_bids(auction_id: &T::Hash, bid_id: &T::Hash) -> Result {
+  // settle an auction that has passed its close time. Callers check the auction can be closed.
+  fn _close_auction(auction_id: T::Hash) -> Result {
     let auction = Self::auctions(auction_id);
-    let bid = Self::bids(bid_id);
 
-    if bid.price < auction.price_to_topmost {
-      return Ok(());
-    }
+    // the highest bidder, if any, wins the auction
+    let sale = match auction.topmost_bids.get(0) {
+      Some(reward_bid_id) => Some((Self::bids(reward_bid_id).bidder, Self::_auction_tx_price(&auction))),
+      None => None,
+    };
 
-    <Auctions<T>>::mutate(auction_id, |auction| {
-      // it could be this bid is a topmost bid already with bid_price being updated
-      if !auction.topmost_bids.contains(bid_id) {
-        auction.topmost_bids.push(bid_id.clone());
-      }
+    Self::_settle_auction(auction_id, sale)
+  }
 
-      // sort the bids
-      auction.topmost_bids.sort_by(|a, b| {
-        let a_bp = Self::bids(a).price;
-        let b_bp = Self::bids(b).price;
-        b_bp.partial_cmp(&a_bp).unwrap()
-      });
+  // settle an auction, with the buyer paying the given price when there is a sale
+  fn _settle_auction(auction_id: T::Hash, sale: Option<(T::AccountId, T::Balance)>) -> Result {
+    // write
+    //   1. check if there is a buyer. If yes
+    //     - unreserve his money,
+    //     - transfer the tx price to the treasury as protocol fee, and the rest to
+    //       kitty_owner and the kitty creator royalty. The rest of his money stays with the buyer
+    //     - update kitty to the buyer
+    //     - emit an event saying an auction with aid has a transaction, of kitty_id
+    //       from AccountId to AccountId
+    //   2. unreserve all fund from the rest of the bidders. Sealed bids that are never
+    //      revealed forfeit part of their deposit to the kitty owner. Return the seller
+    //      listing deposit
+    //   3. set auction status to Closed
+    //     - emit an event saying auction closed
 
-      // drop the last bid if needed
-      auction.topmost_bids = auction.topmost_bids.clone()
-        .into_iter().take(TOPMOST_BIDS_LEN).collect();
+    let now = <timestamp::Module<T>>::get();
+    let auction = Self::auctions(auction_id);
 
-      // update the price_to_topmost. Only update it when the vector is filled
-      if auction.topmost_bids.len() >= TOPMOST_BIDS_LEN {
-        let bid = Self::bids(auction.topmost_bids[TOPMOST_BIDS_LEN - 1]);
-        auction.price_to_topmost = bid.price + <T::Balance as As<u64>>::sa(1);
+    // everything that can fail is checked before the first write, so a failed settlement
+    //   leaves the auction as it is and can be retried
+    let kitty_owner = Self::_auction_admin(auction_id)?;
+    let zero = <T::Balance as As<u64>>::sa(0);
+    let payment = match sale {
+      Some((ref buyer, tx_price)) => {
+        let buyer_bid = if <AuctionBidderBids<T>>::exists((auction_id, buyer.clone())) {
+          Some(Self::bids(Self::auction_bidder_bids((auction_id, buyer.clone()))))
+        } else { None };
+        // the buyer own bid is released first, so his reserved money can pay
+        let released = match buyer_bid {
+          Some(ref bid) if bid.status != BidStatus::Committed => rstd::cmp::min(bid.price + bid.deposit,
+            <balances::Module<T>>::reserved_balance(buyer)),
+          _ => zero,
+        };
+        ensure!(<balances::Module<T>>::free_balance(buyer) + released >= tx_price,
+          Error::BuyerCannotPay.into());
+
+        let fee = Self::_protocol_fee_due(tx_price);
+        let royalty_to = Self::_seller_royalty(&kitty_owner, &auction.kitty_id, tx_price - fee, false)?;
+        Some((buyer.clone(), tx_price, buyer_bid, fee, royalty_to))
+      },
+      None => None,
+    };
+
+    // #1. Transact the kitty and money between buyer and kitty owner
+    let mut auction_tx_opt: Option<AuctionTxOf<T>> = None;
+
+    if let Some((ref buyer, tx_price, buyer_bid, fee, royalty_to)) = payment {
+      if let Some(buyer_bid) = buyer_bid {
+        Self::_release_bid(&buyer_bid, &kitty_owner);
       }
-    });
 
-    Ok(())
-  }
+      let fee = Self::_collect_protocol_fee(buyer, fee);
+      if fee > zero {
+        Self::deposit_event(RawEvent::ProtocolFeeCollected(auction_id, fee));
+      }
+      let royalty = Self::_pay_seller_and_royalty(buyer, &kitty_owner, &auction.kitty_id, tx_price - fee,
+        royalty_to, false)?;
+
+      match auction.item {
+        AuctionItem::Ownership => Self::_transfer_kitty_ownership(&auction.kitty_id, buyer)?,
+        AuctionItem::SiringRight => {
+          // the kitty stays with its owner, the buyer gets the siring right
+          let expires = now.clone() + auction.siring_right_duration.clone();
+          <SiringRights<T>>::insert(auction.kitty_id, SiringRight {
+            grantee: buyer.clone(),
+            expires: expires.clone(),
+          });
+          <Kitties<T>>::mutate(auction.kitty_id, |kitty| kitty.in_auction = false);
+          Self::deposit_event(RawEvent::SiringRightGranted(auction.kitty_id, buyer.clone(), expires));
+        },
+      }
 
-  fn _update_auction_display_bids_nocheck(auction_id: T::Hash, ev: bool) -> Result {
-    let now = <timestamp::Module<T>>::get();
+      // create the auction_tx here
+      auction_tx_opt = Some(AuctionTx {
+        tx_time: now,
+        winner: buyer.clone(),
+        tx_price,
+        fee,
+        royalty,
+      });
+
+      // emit event of the kitty is transferred
+      Self::deposit_event(RawEvent::AuctionTx(auction_id, auction.kitty_id, kitty_owner.clone(), buyer.clone()));
+    } else {
+      // No one bid. So no kitty ownership transfer is made. Resume the kitty to the owner
+      <Kitties<T>>::mutate(auction.kitty_id, |kitty| {
+        kitty.in_auction = false;
+      });
+    }
 
+    // #2. unreserve funds for other bidders
+    let bids_count = <AuctionBidsCount<T>>::get(auction_id);
+    (0..bids_count)
+      .map(|i| Self::bids( Self::auction_bids((auction_id, i)) ) )  // get the bids
+      .filter(|bid| bid.status != BidStatus::Withdrawn)             // withdrawn bids are refunded already
+      .filter(|bid| match &sale {                                   // filter out the buyer
+        Some((buyer, _)) => *buyer != bid.bidder,
+        None => true
+      })
+      .for_each(|bid| Self::_release_bid(&bid, &kitty_owner));      // unreserve funds for other bidders
+    <balances::Module<T>>::unreserve(&kitty_owner, auction.listing_deposit);
+
+    // #3. close the auction and emit event
     <Auctions<T>>::mutate(auction_id, |auction| {
-      auction.display_bids = auction.topmost_bids.clone();
-      auction.display_bids_last_update = now.clone();
+      auction.status = AuctionStatus::Closed;
+      auction.tx = auction_tx_opt;
     });
-    // emit event depends on the passed-in flag
-    if ev {
-      let auction = Self::auctions(auction_id);
-      Self::deposit_event(RawEvent::UpdateDisplayedBids(auction_id, auction.display_bids));
-    }
+    Self::_ongoing_auctions_remove(&auction_id);
+
+    // #4. update the display bid upon closing
+    let _ = Self::_update_auction_display_bids_nocheck(auction_id, false);
+
+    Self::deposit_event(RawEvent::AuctionClosed(auction_id));
 
     Ok(())
   }
 
-  fn _transfer_kitty_ownership(kitty_id: &T::Hash, new_owner_ref: &T::AccountId) {
-    // Need to update:
-    //   1. update OwnerKitties, OwnerKittiesCount of original owner
-    //   2. update OwnerKitties, OwnerKittiesCount of new_owner
-    //   3. update Kitty (owner, owner_pos)
-    let kitty = Self::kitties(kitty_id);
+  // the protocol fee on `price`. A fee too small to open the treasury account is not taken,
+  //   so settlement never fails on it
+  fn _protocol_fee_due(price: T::Balance) -> T::Balance {
+    let fee = Self::protocol_fee() * price;
+    if Self::_can_receive(&Self::treasury_account(), fee, false) { fee } else { <T::Balance as As<u64>>::sa(0) }
+  }
 
-    // 1. update OwnerKitties, OwnerKittiesCount of original owner
-    let orig_kitty_owner = kitty.owner.clone().unwrap();
-    let kitty_cnt = Self::owner_kitties_count(&orig_kitty_owner);
-    let kitty_owner_pos = kitty.owner_pos.unwrap();
+  // pay the protocol fee from `_protocol_fee_due` to the treasury account. Return the fee paid
+  fn _collect_protocol_fee(payer: &T::AccountId, fee: T::Balance) -> T::Balance {
+    let zero = <T::Balance as As<u64>>::sa(0);
+    if fee == zero { return zero; }
 
-    // Two cases: when 1) the kitty is at the last position in OwnerKitties storage, 2) or not
-    if kitty_owner_pos == kitty_cnt - 1 {
-      // transferred kitty is at the last position, just need to remove that from OwnerKitties
-      <OwnerKitties<T>>::remove((orig_kitty_owner.clone(), kitty_cnt - 1));
-    } else {
-      // we move the kitty in the last position to the position of the transferring kitty
-      let last_kitty_id = Self::owner_kitties((orig_kitty_owner.clone(), kitty_cnt - 1));
+    let treasury = Self::treasury_account();
+    if <balances::Module<T> as Currency<_>>::transfer(payer, &treasury, fee).is_err() {
+      return zero;
+    }
+    <TreasuryRevenue<T>>::mutate(|revenue| *revenue = *revenue + fee);
+    fee
+  }
 
-      // update the kitty storage value
-      <Kitties<T>>::mutate(last_kitty_id, |last_kitty| last_kitty.owner_pos = Some(kitty_owner_pos));
+  // slash part of the seller listing deposit to the treasury account. Return the amount
+  //   slashed. When it cannot go to the treasury, it is burnt
+  fn _slash_listing_deposit(seller: &T::AccountId, listing_deposit: T::Balance) -> T::Balance {
+    let slashed = Permill::from_percent(CANCEL_DEPOSIT_SLASH_PERCENT) * listing_deposit;
+    if slashed == <T::Balance as As<u64>>::sa(0) { return slashed; }
 
-      // switch kitty position here
-      <OwnerKitties<T>>::remove((orig_kitty_owner.clone(), kitty_cnt - 1));
-      <OwnerKitties<T>>::insert(
-        (orig_kitty_owner.clone(), kitty_owner_pos),
-        last_kitty_id
-      );
+    let treasury = Self::treasury_account();
+    if <balances::Module<T>>::repatriate_reserved(seller, &treasury, slashed).is_ok() {
+      <TreasurySlashes<T>>::mutate(|slashes| *slashes = *slashes + slashed);
+    } else {
+      <balances::Module<T>>::slash_reserved(seller, slashed);
     }
-    <OwnerKittiesCount<T>>::mutate(&orig_kitty_owner, |cnt| *cnt -= 1);
+    slashed
+  }
 
-    // 2. update OwnerKitties, OwnerKittiesCount of new_owner
-    let kitty_new_pos = Self::owner_kitties_count(new_owner_ref);
-    <OwnerKitties<T>>::insert((new_owner_ref.clone(), kitty_new_pos), kitty_id);
+  // pay `price` from the buyer to the seller. The kitty creator takes the kitty royalty
+  //   out of it, unless the creator is the seller. Return the royalty paid
+  fn _pay_seller(buyer: &T::AccountId, seller: &T::AccountId, kitty_id: &T::Hash,
+    price: T::Balance, from_reserved: bool) -> StdResult<T::Balance>
+  {
+    let royalty_to = Self::_seller_royalty(seller, kitty_id, price, from_reserved)?;
+    Self::_pay_seller_and_royalty(buyer, seller, kitty_id, price, royalty_to, from_reserved)
+  }
+
+  // check the seller and the kitty creator can receive their part of `price`, and return the
+  //   creator and royalty to pay, if any. Both payments are checked before any money moves,
+  //   so a sale is never paid halfway
+  fn _seller_royalty(seller: &T::AccountId, kitty_id: &T::Hash, price: T::Balance, from_reserved: bool)
+    -> StdResult<Option<(T::AccountId, T::Balance)>>
+  {
+    let zero = <T::Balance as As<u64>>::sa(0);
+    let kitty = Self::kitties(kitty_id);
+    // a royalty the creator account cannot receive is not taken, as the protocol fee is
+    let royalty_to = match kitty.creator {
+      Some(creator) => {
+        let royalty = kitty.royalty * price;
+        if creator != *seller && royalty > zero && Self::_can_receive(&creator, royalty, from_reserved) {
+          Some((creator, royalty))
+        } else { None }
+      },
+      None => None,
+    };
+    let royalty = royalty_to.as_ref().map(|(_, royalty)| *royalty).unwrap_or(zero);
+    ensure!(Self::_can_receive(seller, price - royalty, from_reserved),
+      Error::SellerCannotReceive.into());
+    Ok(royalty_to)
+  }
+
+  // pay `price` from the buyer to the seller, less the royalty checked by `_seller_royalty`.
+  //   Return the royalty paid
+  fn _pay_seller_and_royalty(buyer: &T::AccountId, seller: &T::AccountId, kitty_id: &T::Hash,
+    price: T::Balance, royalty_to: Option<(T::AccountId, T::Balance)>, from_reserved: bool)
+    -> StdResult<T::Balance>
+  {
+    let royalty = royalty_to.as_ref().map(|(_, royalty)| *royalty).unwrap_or(<T::Balance as As<u64>>::sa(0));
+    Self::_pay(buyer, seller, price - royalty, from_reserved)?;
+    if let Some((creator, royalty)) = royalty_to {
+      Self::_pay(buyer, &creator, royalty, from_reserved)?;
+      Self::deposit_event(RawEvent::RoyaltyPaid(*kitty_id, creator, royalty));
+    }
+    Ok(royalty)
+  }
+
+  // whether `_pay` can credit `amount` to `to`. Money from a free balance has to reach the
+  //   existential deposit to open an account, and reserved money only moves to an existing one
+  fn _can_receive(to: &T::AccountId, amount: T::Balance, from_reserved: bool) -> bool {
+    let exists = <balances::Module<T> as Currency<_>>::total_balance(to) > <T::Balance as As<u64>>::sa(0);
+    exists || (!from_reserved && amount >= <balances::Module<T>>::existential_deposit())
+  }
+
+  // move money from one account to another, either from its free or reserved balance
+  fn _pay(from: &T::AccountId, to: &T::AccountId, amount: T::Balance, from_reserved: bool) -> Result {
+    if from == to {
+      if from_reserved { <balances::Module<T>>::unreserve(from, amount); }
+      return Ok(());
+    }
+
+    if from_reserved {
+      <balances::Module<T>>::repatriate_reserved(from, to, amount)
+        .map_err(|_| Error::PaymentFailed)?;
+    } else {
+      <balances::Module<T> as Currency<_>>::transfer(from, to, amount)
+        .map_err(|_| Error::PaymentFailed)?;
+    }
+    Ok(())
+  }
+
+  // return the money reserved by a bid. A sealed bid never revealed forfeits part of its
+  //   deposit to the kitty owner.
+  fn _release_bid(bid: &BidOf<T>, kitty_owner: &T::AccountId) {
+    if bid.status == BidStatus::Committed {
+      let forfeit = Permill::from_percent(SEALED_BID_FORFEIT_PERCENT) * bid.deposit;
+      let _ = <balances::Module<T>>::repatriate_reserved(&bid.bidder, kitty_owner, forfeit);
+      <balances::Module<T>>::unreserve(&bid.bidder, bid.deposit - forfeit);
+    } else {
+      <balances::Module<T>>::unreserve(&bid.bidder, bid.price + bid.deposit);
+    }
+  }
+
+  // settle auctions in the expiry queue that have passed their close time, called in `on_finalize`.
+  //   At most MAX_SETTLEMENTS_PER_BLOCK queued auctions are visited per block.
+  fn _settle_expired_auctions() {
+    let (mut bucket, mut index) = match Self::auction_expiry_cursor() {
+      Some(cursor) => cursor,
+      None => return,
+    };
+    let now = <timestamp::Module<T>>::get();
+    let now_bucket = now.as_() / SCHEDULE_BUCKET_SECS;
+    let mut budget = MAX_SETTLEMENTS_PER_BLOCK;
+    let mut buckets_visited = 0;
+
+    while bucket <= now_bucket && budget > 0 && buckets_visited < MAX_EXPIRY_BUCKETS_PER_BLOCK {
+      let len = Self::auction_expiry_queue_len(bucket);
+
+      while index < len && budget > 0 {
+        let auction_id = <AuctionExpiryQueue<T>>::take((bucket, index));
+        index += 1;
+        budget -= 1;
+
+        // auction closed manually or cancelled already
+        let auction = Self::auctions(auction_id);
+        if auction.status != AuctionStatus::Ongoing { continue; }
+
+        // auction close time not reached yet, or it has been moved to a later bucket. Queue it
+        //   again, at the back of the bucket of its close time
+        let close_time = Self::_auction_close_time(&auction);
+        if close_time > now {
+          Self::_enqueue_auction_expiry(auction_id, close_time);
+          continue;
+        }
+
+        // a failed settlement leaves the auction untouched. It is retried in a later bucket,
+        //   and can still be closed by `close_auction_and_tx`
+        if Self::_close_auction(auction_id).is_err() {
+          Self::_enqueue_auction_expiry(auction_id, now.clone() + <T::Moment as As<u64>>::sa(SCHEDULE_BUCKET_SECS));
+        }
+      }
+
+      // this bucket is not done, or still gets auctions queued. Carry it over to the next block
+      if index < Self::auction_expiry_queue_len(bucket) || bucket == now_bucket { break; }
+
+      <AuctionExpiryQueueLen<T>>::remove(bucket);
+      bucket += 1;
+      index = 0;
+      buckets_visited += 1;
+    }
+
+    <AuctionExpiryCursor<T>>::put((bucket, index));
+  }
+
+  fn _enqueue_auction_expiry(auction_id: T::Hash, close_time: T::Moment) {
+    // the cursor starts at the bucket of the current time, and only moves forward as buckets
+    //   are drained. An auction closing behind it is past due, and is queued at the cursor
+    let cursor = match Self::auction_expiry_cursor() {
+      Some((cursor, _)) => cursor,
+      None => {
+        let now_bucket = <timestamp::Module<T>>::get().as_() / SCHEDULE_BUCKET_SECS;
+        <AuctionExpiryCursor<T>>::put((now_bucket, 0));
+        now_bucket
+      },
+    };
+    let bucket = rstd::cmp::max(close_time.as_() / SCHEDULE_BUCKET_SECS, cursor);
+    let index = Self::auction_expiry_queue_len(bucket);
+    <AuctionExpiryQueue<T>>::insert((bucket, index), auction_id);
+    <AuctionExpiryQueueLen<T>>::insert(bucket, index + 1);
+  }
+
+  // refresh display bids of ongoing auctions that are due, called in `on_finalize`.
+  //   At most MAX_DISPLAY_REFRESHES_PER_BLOCK queued auctions are visited per block.
+  fn _refresh_due_display_bids() {
+    let (mut bucket, mut index) = match Self::display_refresh_cursor() {
+      Some(cursor) => cursor,
+      None => return,
+    };
+    let now = <timestamp::Module<T>>::get();
+    let now_bucket = now.as_() / SCHEDULE_BUCKET_SECS;
+    let mut budget = MAX_DISPLAY_REFRESHES_PER_BLOCK;
+    let mut buckets_visited = 0;
+
+    while bucket <= now_bucket && budget > 0 && buckets_visited < MAX_DISPLAY_REFRESH_BUCKETS_PER_BLOCK {
+      let len = Self::display_refresh_queue_len(bucket);
+
+      while index < len && budget > 0 {
+        let auction_id = <DisplayRefreshQueue<T>>::take((bucket, index));
+        index += 1;
+        budget -= 1;
+
+        // auction is over, it is no longer refreshed
+        if Self::auctions(auction_id).status != AuctionStatus::Ongoing {
+          <NextDisplayRefresh<T>>::remove(auction_id);
+          continue;
+        }
+
+        // refresh time not reached yet, or it has been moved by a manual update. Queue it
+        //   again, at the back of the bucket of its refresh time
+        let next_refresh = match Self::next_display_refresh(auction_id) {
+          Some(next_refresh) => next_refresh,
+          None => continue,
+        };
+        if next_refresh > now {
+          Self::_enqueue_display_refresh(auction_id, next_refresh);
+          continue;
+        }
+
+        let _ = Self::_update_auction_display_bids_nocheck(auction_id, true);
+        if let Some(next_refresh) = Self::next_display_refresh(auction_id) {
+          Self::_enqueue_display_refresh(auction_id, next_refresh);
+        }
+      }
+
+      // this bucket is not done, or still gets auctions queued. Carry it over to the next block
+      if index < Self::display_refresh_queue_len(bucket) || bucket == now_bucket { break; }
+
+      <DisplayRefreshQueueLen<T>>::remove(bucket);
+      bucket += 1;
+      index = 0;
+      buckets_visited += 1;
+    }
+
+    <DisplayRefreshCursor<T>>::put((bucket, index));
+  }
+
+  fn _enqueue_display_refresh(auction_id: T::Hash, refresh_time: T::Moment) {
+    // as the expiry cursor, it starts at the bucket of the current time and only moves forward
+    let cursor = match Self::display_refresh_cursor() {
+      Some((cursor, _)) => cursor,
+      None => {
+        let now_bucket = <timestamp::Module<T>>::get().as_() / SCHEDULE_BUCKET_SECS;
+        <DisplayRefreshCursor<T>>::put((now_bucket, 0));
+        now_bucket
+      },
+    };
+    let bucket = rstd::cmp::max(refresh_time.as_() / SCHEDULE_BUCKET_SECS, cursor);
+    let index = Self::display_refresh_queue_len(bucket);
+    <DisplayRefreshQueue<T>>::insert((bucket, index), auction_id);
+    <DisplayRefreshQueueLen<T>>::insert(bucket, index + 1);
+  }
+
+  // the current price of a dutch auction
+  fn _dutch_price(auction: &AuctionOf<T>, now: T::Moment) -> T::Balance {
+    let duration = auction.end_time.as_() - auction.start_time.as_();
+    let mut elapsed = now.as_().saturating_sub(auction.start_time.as_());
+    let step = auction.dutch_step.as_();
+
+    // the price only falls at the end of each step
+    if step > 0 { elapsed -= elapsed % step; }
+    if elapsed >= duration { return auction.base_price; }
+
+    let price_drop = (auction.dutch_start_price - auction.base_price) *
+      <T::Balance as As<u64>>::sa(elapsed) / <T::Balance as As<u64>>::sa(duration);
+    auction.dutch_start_price - price_drop
+  }
+
+  // the time an auction can be closed. Sealed-bid auction is closed only after its reveal window.
+  fn _auction_close_time(auction: &AuctionOf<T>) -> T::Moment {
+    match auction.kind {
+      AuctionKind::English => auction.end_time,
+      AuctionKind::Sealed => auction.reveal_end_time,
+      AuctionKind::Dutch => auction.end_time,
+    }
+  }
+
+  fn _auction_admin(auction_id: T::Hash) -> StdResult<T::AccountId> {
+    // we use an internal function here, so later on we can modify the logic
+    //   how an auction admin is determined.
+
+    let auction = Self::auctions(auction_id);
+    let kitty = Self::kitties(auction.kitty_id);
+    kitty.owner.ok_or(Error::KittyNoOwner.into())
+  }
+
+  // module calls wait until storage is migrated to the current layout, as values not migrated
+  //   yet do not decode
+  fn _ensure_storage_migrated() -> Result {
+    ensure!(Self::storage_version() == STORAGE_VERSION, Error::StorageMigrating.into());
+    Ok(())
+  }
+
+  fn _update_auction_topmost_bids(auction_id: &T::Hash, bid_id: &T::Hash) -> Result {
+    let auction = Self::auctions(auction_id);
+    let bid = Self::bids(bid_id);
+    let topmost_bids_len = Self::topmost_bids_len() as usize;
+
+    // topmost bids not filled while the auction has other bids than them and this one: root
+    //   raised the topmost bids length after those were dropped, so they are reconsidered
+    if bid.status == BidStatus::Active && auction.topmost_bids.len() < topmost_bids_len &&
+      Self::auction_bids_count(auction_id) > auction.topmost_bids.len() as u64 + 1
+    {
+      return Self::_rebuild_auction_topmost_bids(auction_id);
+    }
+
+    // `price_to_topmost` only holds while the topmost bids are filled. They are not after
+    //   root raises the topmost bids length
+    if bid.status != BidStatus::Active ||
+      (auction.topmost_bids.len() >= topmost_bids_len && bid.price < auction.price_to_topmost)
+    {
+      return Ok(());
+    }
+
+    <Auctions<T>>::mutate(auction_id, |auction| {
+      // it could be this bid is a topmost bid already with bid_price being updated
+      if !auction.topmost_bids.contains(bid_id) {
+        auction.topmost_bids.push(bid_id.clone());
+      }
+
+      // sort the bids
+      auction.topmost_bids.sort_by(|a, b| {
+        let a_bp = Self::bids(a).price;
+        let b_bp = Self::bids(b).price;
+        b_bp.partial_cmp(&a_bp).unwrap()
+      });
+
+      // drop the last bid if needed
+      auction.topmost_bids = auction.topmost_bids.clone()
+        .into_iter().take(topmost_bids_len).collect();
+
+      // update the price_to_topmost. Only update it when the vector is filled
+      if auction.topmost_bids.len() >= topmost_bids_len {
+        let bid = Self::bids(auction.topmost_bids[topmost_bids_len - 1]);
+        auction.price_to_topmost = bid.price + <T::Balance as As<u64>>::sa(1);
+      }
+    });
+
+    Ok(())
+  }
+
+  // recompute the topmost bids from all active bids of the auction. Used when a bid
+  //   drops out of the topmost bids, e.g. being withdrawn.
+  fn _rebuild_auction_topmost_bids(auction_id: &T::Hash) -> Result {
+    let auction = Self::auctions(auction_id);
+    let bids_count = Self::auction_bids_count(auction_id);
+    let topmost_bids_len = Self::topmost_bids_len() as usize;
+
+    let mut active_bids: Vec<BidOf<T>> = (0..bids_count)
+      .map(|i| Self::bids( Self::auction_bids((*auction_id, i)) ))
+      .filter(|bid| bid.status == BidStatus::Active)
+      .collect();
+
+    // sort by price descending. Stable sort keeps the earlier bid ahead on ties
+    active_bids.sort_by(|a, b| b.price.partial_cmp(&a.price).unwrap());
+
+    let topmost_bids: Vec<T::Hash> = active_bids.iter()
+      .take(topmost_bids_len).map(|bid| bid.id).collect();
+
+    let price_to_topmost = if topmost_bids.len() >= topmost_bids_len {
+      active_bids[topmost_bids_len - 1].price + <T::Balance as As<u64>>::sa(1)
+    } else {
+      auction.base_price
+    };
+
+    <Auctions<T>>::mutate(auction_id, |auction| {
+      auction.topmost_bids = topmost_bids;
+      auction.price_to_topmost = price_to_topmost;
+    });
+
+    Ok(())
+  }
+
+  fn _update_auction_display_bids_nocheck(auction_id: T::Hash, ev: bool) -> Result {
+    let now = <timestamp::Module<T>>::get();
+
+    <Auctions<T>>::mutate(auction_id, |auction| {
+      auction.display_bids = auction.topmost_bids.clone();
+      auction.display_bids_last_update = now.clone();
+    });
+    // the queued refresh entry is moved to this time when it is visited
+    if <NextDisplayRefresh<T>>::exists(auction_id) {
+      <NextDisplayRefresh<T>>::insert(auction_id,
+        now.clone() + <T::Moment as As<u64>>::sa(Self::display_bids_update_period()));
+    }
+    // emit event depends on the passed-in flag
+    if ev {
+      let auction = Self::auctions(auction_id);
+      Self::deposit_event(RawEvent::UpdateDisplayedBids(auction_id, auction.display_bids));
+    }
+
+    Ok(())
+  }
+
+  // remove the offer from storage. The last offer in each index takes the position of the
+  //   removed one
+  fn _remove_offer(offer: &OfferOf<T>) {
+    // 1. KittyOffers, KittyOffersCount
+    let last_pos = Self::kitty_offers_count(offer.kitty_id) - 1;
+    if offer.kitty_pos != last_pos {
+      let last_offer_id = Self::kitty_offers((offer.kitty_id, last_pos));
+      <Offers<T>>::mutate(last_offer_id, |last_offer| last_offer.kitty_pos = offer.kitty_pos);
+      <KittyOffers<T>>::insert((offer.kitty_id, offer.kitty_pos), last_offer_id);
+    }
+    <KittyOffers<T>>::remove((offer.kitty_id, last_pos));
+    <KittyOffersCount<T>>::insert(offer.kitty_id, last_pos);
+
+    // 2. OffererOffers, OffererOffersCount
+    let last_pos = Self::offerer_offers_count(&offer.offerer) - 1;
+    if offer.offerer_pos != last_pos {
+      let last_offer_id = Self::offerer_offers((offer.offerer.clone(), last_pos));
+      <Offers<T>>::mutate(last_offer_id, |last_offer| last_offer.offerer_pos = offer.offerer_pos);
+      <OffererOffers<T>>::insert((offer.offerer.clone(), offer.offerer_pos), last_offer_id);
+    }
+    <OffererOffers<T>>::remove((offer.offerer.clone(), last_pos));
+    <OffererOffersCount<T>>::insert(&offer.offerer, last_pos);
+
+    // 3. the offer itself
+    <Offers<T>>::remove(offer.id);
+  }
+
+  fn _transfer_kitty_ownership(kitty_id: &T::Hash, new_owner_ref: &T::AccountId) -> Result {
+    // Need to update:
+    //   1. move the kitty from the owned kitties of the original owner to the new owner
+    //   2. remove the siring approval, expired siring right and fixed-price listing of the kitty
+    //   3. update Kitty owner
+    let kitty = Self::kitties(kitty_id);
+    let orig_kitty_owner = kitty.owner.ok_or(Error::KittyNoOwner)?;
+
+    // 1. update OwnedKitties, OwnerKittiesCount
+    Self::_owned_kitties_remove(&orig_kitty_owner, kitty_id);
+    <OwnerKittiesCount<T>>::mutate(&orig_kitty_owner, |cnt| *cnt -= 1);
+    Self::_owned_kitties_append(new_owner_ref, kitty_id);
     <OwnerKittiesCount<T>>::mutate(new_owner_ref, |cnt| *cnt += 1);
 
+    // 2. siring approval given by the original owner no longer holds
+    <SiringApprovals<T>>::remove(kitty_id);
+    <SiringRights<T>>::remove(kitty_id);
+    <KittyPrices<T>>::remove(kitty_id);
+
     // 3. update the kitty
     <Kitties<T>>::mutate(kitty_id, |kitty| {
       kitty.owner = Some(new_owner_ref.clone());
-      kitty.owner_pos = Some(kitty_new_pos);
       kitty.in_auction = false;
     });
+    Ok(())
+  }
+
+  // kitty ids of `owner`, in the order they are added to the owner
+  pub fn owned_kitty_ids(owner: &T::AccountId) -> Vec<T::Hash> {
+    let mut kitty_ids = Vec::new();
+    let mut next = Self::_owned_kitties_item(owner, None).next;
+    while let Some(kitty_id) = next {
+      kitty_ids.push(kitty_id);
+      next = Self::_owned_kitties_item(owner, Some(kitty_id)).next;
+    }
+    kitty_ids
+  }
+
+  fn _owned_kitties_item(owner: &T::AccountId, kitty_id: Option<T::Hash>) -> KittyLinkedItem<T::Hash> {
+    Self::owned_kitties((owner.clone(), kitty_id)).unwrap_or_default()
+  }
+
+  // add the kitty at the end of the owner kitty list. Writing the last item goes through the
+  //   head when the list is empty, so the head `next` is set to the kitty too
+  fn _owned_kitties_append(owner: &T::AccountId, kitty_id: &T::Hash) {
+    let head = Self::_owned_kitties_item(owner, None);
+    <OwnedKitties<T>>::insert((owner.clone(), None), KittyLinkedItem {
+      prev: Some(*kitty_id),
+      next: head.next,
+    });
+
+    let last = Self::_owned_kitties_item(owner, head.prev);
+    <OwnedKitties<T>>::insert((owner.clone(), head.prev), KittyLinkedItem {
+      prev: last.prev,
+      next: Some(*kitty_id),
+    });
+
+    <OwnedKitties<T>>::insert((owner.clone(), Some(*kitty_id)), KittyLinkedItem {
+      prev: head.prev,
+      next: None,
+    });
+  }
+
+  // unlink the kitty from the owner kitty list, joining its neighbours
+  fn _owned_kitties_remove(owner: &T::AccountId, kitty_id: &T::Hash) {
+    if let Some(item) = <OwnedKitties<T>>::take((owner.clone(), Some(*kitty_id))) {
+      let prev = Self::_owned_kitties_item(owner, item.prev);
+      <OwnedKitties<T>>::insert((owner.clone(), item.prev), KittyLinkedItem {
+        prev: prev.prev,
+        next: item.next,
+      });
+
+      let next = Self::_owned_kitties_item(owner, item.next);
+      <OwnedKitties<T>>::insert((owner.clone(), item.next), KittyLinkedItem {
+        prev: item.prev,
+        next: next.next,
+      });
+    }
+  }
+
+  // raw storage key of an item in an earlier storage layout, which has no typed accessor anymore
+  fn _legacy_storage_key<K: Encode>(item: &[u8], key: &K) -> Vec<u8> {
+    let mut storage_key = b"CatAuction ".to_vec();
+    storage_key.extend_from_slice(item);
+    key.encode_to(&mut storage_key);
+    storage_key
+  }
+
+  // run a bounded batch of the migration from the storage version in place to the next one.
+  //   Called at the start of every block, so it picks up a runtime upgrade by `set_code`
+  fn _migrate_storage() {
+    // storage before versioning is v1. A new chain has nothing to migrate and gets to
+    //   the current version in its first block
+    let version = rstd::cmp::max(Self::storage_version(), 1);
+    if version >= STORAGE_VERSION { return; }
+
+    let done = match version {
+      1 => Self::_migrate_v1_to_v2(MAX_MIGRATIONS_PER_BLOCK),
+      _ => true,
+    };
+
+    if done {
+      <StorageVersion<T>>::put(version + 1);
+      <MigrationCursor<T>>::kill();
+      Self::deposit_event(RawEvent::StorageMigrated(version + 1));
+    }
+  }
+
+  // v1 -> v2: upgrade kitties, auctions and bids to their v2 layout. Kitties move from the
+  //   `OwnerKitties` index and `Kitty.owner_pos` to the owner linked lists, and ongoing auctions
+  //   are queued for settlement and display bids refresh. Kitties are visited first, then each
+  //   auction after its bids. Return whether all values are migrated
+  fn _migrate_v1_to_v2(max_values: u64) -> bool {
+    let kitties_count = Self::kitties_count();
+    let values_count = kitties_count + Self::auctions_count();
+    let (mut index, mut bid_index) = Self::migration_cursor();
+    let mut budget = max_values;
+
+    while budget > 0 && index < values_count {
+      budget -= 1;
+      if index < kitties_count {
+        Self::_migrate_kitty_v1(Self::kitty_array(index));
+        index += 1;
+        continue;
+      }
+
+      let auction_id = Self::auction_array(index - kitties_count);
+      if bid_index < Self::auction_bids_count(auction_id) {
+        Self::_migrate_bid_v1(Self::auction_bids((auction_id, bid_index)));
+        bid_index += 1;
+      } else {
+        Self::_migrate_auction_v1(auction_id);
+        index += 1;
+        bid_index = 0;
+      }
+    }
+
+    <MigrationCursor<T>>::put((index, bid_index));
+    index >= values_count
+  }
+
+  fn _migrate_kitty_v1(kitty_id: T::Hash) {
+    let kitty_v1 = match support::storage::get::<KittyV1Of<T>>(
+      &Self::_legacy_storage_key(b"Kitties", &kitty_id)) {
+      Some(kitty_v1) => kitty_v1,
+      None => return,
+    };
+
+    let (kitty, owner_pos) = kitty_v1.upgrade();
+    if let (Some(owner), Some(pos)) = (kitty.owner.clone(), owner_pos) {
+      // `OwnerKittiesCount` stays the same
+      support::storage::kill(&Self::_legacy_storage_key(b"OwnerKitties", &(owner.clone(), pos)));
+      Self::_owned_kitties_append(&owner, &kitty_id);
+    }
+    <Kitties<T>>::insert(kitty_id, kitty);
+  }
+
+  fn _migrate_auction_v1(auction_id: T::Hash) {
+    let auction = match support::storage::get::<AuctionV1Of<T>>(
+      &Self::_legacy_storage_key(b"Auctions", &auction_id)) {
+      Some(auction_v1) => auction_v1.upgrade(),
+      None => return,
+    };
+
+    // v1 auctions are settled by a call, so ongoing ones are not queued yet. Neither are they
+    //   indexed for queries
+    if auction.status == AuctionStatus::Ongoing {
+      Self::_ongoing_auctions_insert(&auction_id);
+      Self::_schedule_auction(&auction);
+    }
+    <Auctions<T>>::insert(auction_id, auction);
+  }
+
+  fn _migrate_bid_v1(bid_id: T::Hash) {
+    if let Some(bid_v1) = support::storage::get::<BidV1Of<T>>(
+      &Self::_legacy_storage_key(b"Bids", &bid_id)) {
+      let bid = bid_v1.upgrade();
+      // bids not withdrawn are kept in the auction bidder index
+      if Self::auction_bidder_bids((bid.auction_id, bid.bidder.clone())) == bid_id {
+        Self::_bidder_bids_insert(&bid.bidder, &bid_id);
+      }
+      <Bids<T>>::insert(bid_id, bid);
+    }
   }
 }
 
@@ -600,11 +2777,11 @@ impl<T: Trait> Module<T> {
 mod tests {
   // Test Codes
   use super::*;
-  use support::{ impl_outer_origin, assert_ok };
+  use support::{ impl_outer_origin, assert_ok, assert_noop };
   use runtime_io::{ with_externalities, TestExternalities };
   use primitives::{ H256, Blake2Hasher };
   use runtime_primitives::{
-    BuildStorage, traits::{BlakeTwo256, IdentityLookup},
+    BuildStorage, traits::{BlakeTwo256, IdentityLookup, OnInitialize, OnFinalize},
     testing::{Digest, DigestItem, Header}
   };
 
@@ -669,6 +2846,11 @@ mod tests {
 
   // construct genesis storage
   fn build_ext() -> TestExternalities<Blake2Hasher> {
+    build_ext_with_kitties(vec![])
+  }
+
+  // construct genesis storage with kitties minted at genesis
+  fn build_ext_with_kitties(kitties: Vec<(u64, Vec<u8>, H256)>) -> TestExternalities<Blake2Hasher> {
     let mut t = system::GenesisConfig::<CatAuctionTest>::default().build_storage().unwrap().0;
 
     t.extend(balances::GenesisConfig::<CatAuctionTest> {
@@ -679,9 +2861,25 @@ mod tests {
       ..Default::default()
     }.build_storage().unwrap().0);
 
+    t.extend(GenesisConfig::<CatAuctionTest> { kitties, ..Default::default() }.build_storage().unwrap().0);
+
     t.into()
   }
 
+  // create a kitty for `owner` and put it in auction. Returns (kitty_id, auction_id, end_time)
+  fn create_kitty_in_auction(owner: u64, pricing_rule: PricingRule) -> (H256, H256, u64) {
+    let kitty_name_in_hex = KITTY_NAMES[0].as_bytes().to_vec();
+    assert_ok!(CatAuction::create_kitty(Origin::signed(owner), kitty_name_in_hex, Permill::zero()));
+    let kitty_id = CatAuction::kitty_array(CatAuction::kitties_count() - 1);
+
+    let end_time = <timestamp::Module<CatAuctionTest>>::get() + DEFAULT_AUCTION_MIN_DURATION + 5;
+    assert_ok!(CatAuction::start_auction(Origin::signed(owner), kitty_id, end_time, BASE_PRICE,
+      pricing_rule, None, None, None));
+    let auction_id = CatAuction::auction_array(CatAuction::auctions_count() - 1);
+
+    (kitty_id, auction_id, end_time)
+  }
+
   #[test]
   fn it_works() {
     // Test case to test all test mocks are setup properly
@@ -694,34 +2892,66 @@ mod tests {
   fn can_create_kitty() {
     with_externalities(&mut build_ext(), || {
       let kitty_name_in_hex = KITTY_NAMES[0].as_bytes().to_vec();
-      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name_in_hex));
+      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name_in_hex, Permill::zero()));
 
       assert_eq!(CatAuction::kitties_count(), 1);
       assert_eq!(CatAuction::owner_kitties_count(ALICE), 1);
 
       let kitty_id = CatAuction::kitty_array(0);
-      assert_eq!(CatAuction::owner_kitties((ALICE, 0)), kitty_id);
+      assert_eq!(CatAuction::owned_kitty_ids(&ALICE), vec![kitty_id]);
 
       // test kitty object data is consistent
       let kitty = CatAuction::kitties(kitty_id);
       assert_eq!(kitty.in_auction, false);
       assert_eq!(kitty.owner, Some(ALICE));
-      assert_eq!(kitty.owner_pos, Some(0));
+      assert_eq!(kitty.traits, KittyTraits::from_dna(kitty.dna.as_ref()));
+      assert_eq!(kitty.creator, Some(ALICE));
+
+      // another kitty gets a different DNA
+      let kitty_name_in_hex = KITTY_NAMES[1].as_bytes().to_vec();
+      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name_in_hex, Permill::zero()));
+      assert!(CatAuction::kitties(CatAuction::kitty_array(1)).dna != kitty.dna);
     })
   } // finish test `can_start_auction`
 
+  #[test]
+  fn kitty_dna_is_not_derived_from_its_id() {
+    with_externalities(&mut build_ext(), || {
+      for kitty_name in KITTY_NAMES[0..2].iter() {
+        assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name.as_bytes().to_vec(), Permill::zero()));
+      }
+      let kitty_a = CatAuction::kitties(CatAuction::kitty_array(0));
+      let kitty_b = CatAuction::kitties(CatAuction::kitty_array(1));
+
+      // the ID of the next kitty is not its DNA either
+      assert!(kitty_a.dna != kitty_a.id && kitty_a.dna != kitty_b.id);
+      assert!(kitty_a.dna != kitty_b.dna);
+    });
+  }
+
+  #[test]
+  fn kitty_traits_decode_from_dna() {
+    let traits = KittyTraits::from_dna(&[3, 0x12, 0x34, 0x56, 200, 0, 0]);
+    assert_eq!(traits, KittyTraits { coat: Coat::Siamese, color: 0x123456, rarity: Rarity::Rare });
+
+    let traits = KittyTraits::from_dna(&[9, 0, 0, 0, 255]);
+    assert_eq!(traits.coat, Coat::Sphynx);
+    assert_eq!(traits.rarity, Rarity::Legendary);
+  }
+
   #[test]
   fn can_start_auction_n_bid_n_close() {
     with_externalities(&mut build_ext(), || {
       let kitty_name_in_hex = KITTY_NAMES[0].as_bytes().to_vec();
-      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name_in_hex));
+      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name_in_hex, Permill::zero()));
 
       let kitty_id = CatAuction::kitty_array(0);
       let time_buffer = 5; // 5s for time buffer
       let end_time = <timestamp::Module<CatAuctionTest>>::get() +
-        AUCTION_MIN_DURATION + time_buffer;
+        DEFAULT_AUCTION_MIN_DURATION + time_buffer;
 
-      assert_ok!(CatAuction::start_auction(Origin::signed(ALICE), kitty_id, end_time, BASE_PRICE));
+      assert_ok!(CatAuction::start_auction(Origin::signed(ALICE), kitty_id, end_time, BASE_PRICE,
+        PricingRule::FirstPrice, None, None, None));
 
       // Test auction:
       //   1. auctions_count
@@ -743,7 +2973,7 @@ mod tests {
       //   2. payment is transferred
       //   3. kitty object
       //   4. OwnerKittiesCount (A, B)
-      //   5. OwnerKitties (A, B)
+      //   5. OwnedKitties (A, B)
 
       // check #1: auction object
       let auction = CatAuction::auctions(auction_id);
@@ -751,6 +2981,7 @@ mod tests {
       let auction_tx = auction.tx.unwrap();
       assert_eq!(auction_tx.winner, BOB);
       assert_eq!(auction_tx.tx_price, BASE_PRICE);
+      assert!(CatAuction::ongoing_auctions(0, 10).is_empty());
 
       // check #2: payment is transferred
       let abal_after_bid = <balances::Module<CatAuctionTest>>::free_balance(ALICE);
@@ -762,22 +2993,1322 @@ mod tests {
       let kitty = CatAuction::kitties(kitty_id);
       assert!(!kitty.in_auction);
       assert_eq!(kitty.owner, Some(BOB));
-      assert_eq!(kitty.owner_pos, Some(0));
 
       // check #4: check OwnerKittiesCount
       assert_eq!(CatAuction::owner_kitties_count(ALICE), 0);
       assert_eq!(CatAuction::owner_kitties_count(BOB), 1);
 
-      // check #5: check OwnerKitties
-      assert!(!<OwnerKitties<CatAuctionTest>>::exists((ALICE, 0)));
-      assert!(<OwnerKitties<CatAuctionTest>>::exists((BOB, 0)));
-      assert_eq!(CatAuction::owner_kitties((BOB, 0)), kitty_id);
+      // check #5: check OwnedKitties
+      assert!(!<OwnedKitties<CatAuctionTest>>::exists((ALICE, Some(kitty_id))));
+      assert_eq!(CatAuction::owned_kitty_ids(&ALICE), Vec::<H256>::new());
+      assert_eq!(CatAuction::owned_kitty_ids(&BOB), vec![kitty_id]);
+    });
+  }
+
+  #[test]
+  fn auction_calls_are_rejected_with_error_codes() {
+    with_externalities(&mut build_ext(), || {
+      let (kitty_id, auction_id, end_time) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+
+      assert_noop!(CatAuction::start_auction(Origin::signed(ALICE), kitty_id, end_time, BASE_PRICE,
+        PricingRule::FirstPrice, None, None, None), Error::KittyInAuction.into());
+      assert_noop!(CatAuction::bid(Origin::signed(ALICE), auction_id, BASE_PRICE),
+        Error::OwnerCannotBid.into());
+      assert_noop!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE - 1),
+        Error::BidBelowBasePrice.into());
+      assert_noop!(CatAuction::cancel_auction(Origin::signed(BOB), auction_id),
+        Error::NotAuctionAdmin.into());
+
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+      assert_noop!(CatAuction::cancel_auction(Origin::signed(ALICE), auction_id),
+        Error::AuctionHasBids.into());
+      assert_noop!(CatAuction::close_auction_and_tx(Origin::INHERENT, H256::from([9; 32])),
+        Error::AuctionNotExist.into());
+
+      // sibling auction calls reject with codes too
+      assert_noop!(CatAuction::buy_now(Origin::signed(CHARLES), auction_id), Error::NoBuyNowPrice.into());
+      assert_noop!(CatAuction::accept_price(Origin::signed(CHARLES), auction_id),
+        Error::NotDutchAuction.into());
+      assert_noop!(CatAuction::commit_bid(Origin::signed(CHARLES), auction_id, H256::from([1; 32])),
+        Error::NotSealedAuction.into());
+      assert_noop!(CatAuction::withdraw_bid(Origin::signed(CHARLES), auction_id),
+        Error::NoBidInAuction.into());
+      assert_noop!(CatAuction::update_auction_display_bids(Origin::INHERENT, H256::from([9; 32])),
+        Error::AuctionNotExist.into());
+
+      // the code can be read back from the dispatch result
+      let err = CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE).unwrap_err();
+      assert!(err.starts_with("CatAuction#24:"));
+      assert_eq!(Error::from_message(err), Some(Error::BidNotAbovePrevious));
+      assert_eq!(Error::BidNotAbovePrevious.code(), 24);
+
+      // codes are stable and unique
+      for (i, err) in Error::ALL.iter().enumerate() {
+        assert_eq!(err.code() as usize, i + 1);
+        assert!(err.message().starts_with(&format!("CatAuction#{}:", i + 1)));
+      }
+
+      // every variant is listed: the codes decoding to a variant are exactly those of `ALL`
+      let variants: Vec<Error> = (0..=u8::max_value())
+        .filter_map(|code| Error::decode(&mut &[code][..]))
+        .collect();
+      assert_eq!(variants, Error::ALL.to_vec());
+    });
+  }
+
+  #[test]
+  fn can_withdraw_bid_not_displayed() {
+    with_externalities(&mut build_ext(), || {
+      let (kitty_id, auction_id, end_time) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+      assert_ok!(CatAuction::bid(Origin::signed(CHARLES), auction_id, BASE_PRICE + 1000));
+      assert_ok!(CatAuction::bid(Origin::signed(DAVE), auction_id, BASE_PRICE + 2000));
+      assert_ok!(CatAuction::bid(Origin::signed(EVE), auction_id, BASE_PRICE + 3000));
+
+      // publish the display bids: Eve, Dave, Charles
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD);
+      assert_ok!(CatAuction::update_auction_display_bids(Origin::INHERENT, auction_id));
+
+      // Charles is displayed, so cannot withdraw
+      assert_noop!(CatAuction::withdraw_bid(Origin::signed(CHARLES), auction_id),
+        Error::DisplayedBidWithdrawal.into());
+
+      // Bob is not displayed, and gets his money back
+      let bob_bid_id = CatAuction::auction_bidder_bids((auction_id, BOB));
+      assert_ok!(CatAuction::withdraw_bid(Origin::signed(BOB), auction_id));
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(BOB), INI_BALANCE);
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(BOB), 0);
+      assert_eq!(CatAuction::bids(bob_bid_id).status, BidStatus::Withdrawn);
+      assert!(!<AuctionBidderBids<CatAuctionTest>>::exists((auction_id, BOB)));
+      assert_noop!(CatAuction::withdraw_bid(Origin::signed(BOB), auction_id),
+        Error::NoBidInAuction.into());
+
+      // Bob bids again to the top, but is not displayed yet, so he can still withdraw
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE + 5000));
+      let bob_bid_id = CatAuction::auction_bidder_bids((auction_id, BOB));
+      assert_eq!(CatAuction::auctions(auction_id).topmost_bids[0], bob_bid_id);
+      assert_ok!(CatAuction::withdraw_bid(Origin::signed(BOB), auction_id));
+
+      // the topmost bids are rebuilt without Bob
+      let auction = CatAuction::auctions(auction_id);
+      assert_eq!(auction.topmost_bids, auction.display_bids);
+      assert!(!auction.topmost_bids.contains(&bob_bid_id));
+
+      // close the auction, Eve wins and Bob is not refunded twice
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      assert_ok!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id));
+
+      assert_eq!(CatAuction::kitties(kitty_id).owner, Some(EVE));
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(BOB), INI_BALANCE);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(CHARLES), INI_BALANCE);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(EVE), INI_BALANCE - BASE_PRICE - 3000);
+    });
+  }
+
+  #[test]
+  fn second_price_with_one_bidder_pays_base_price() {
+    with_externalities(&mut build_ext(), || {
+      let (kitty_id, auction_id, end_time) = create_kitty_in_auction(ALICE, PricingRule::SecondPrice);
+
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE + 5000));
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      assert_ok!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id));
+
+      assert_eq!(CatAuction::auctions(auction_id).tx.unwrap().tx_price, BASE_PRICE);
+      assert_eq!(CatAuction::kitties(kitty_id).owner, Some(BOB));
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), INI_BALANCE + BASE_PRICE);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(BOB), INI_BALANCE - BASE_PRICE);
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(BOB), 0);
+    });
+  }
+
+  #[test]
+  fn second_price_with_tied_bids_pays_tied_price() {
+    with_externalities(&mut build_ext(), || {
+      let (kitty_id, auction_id, end_time) = create_kitty_in_auction(ALICE, PricingRule::SecondPrice);
+      let tied_price = BASE_PRICE + 2000;
+
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, tied_price));
+      assert_ok!(CatAuction::bid(Origin::signed(CHARLES), auction_id, tied_price));
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      assert_ok!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id));
+
+      // the earlier bid wins the tie, and pays the tied price
+      let auction_tx = CatAuction::auctions(auction_id).tx.unwrap();
+      assert_eq!(auction_tx.winner, BOB);
+      assert_eq!(auction_tx.tx_price, tied_price);
+      assert_eq!(CatAuction::kitties(kitty_id).owner, Some(BOB));
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(BOB), INI_BALANCE - tied_price);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(CHARLES), INI_BALANCE);
+    });
+  }
+
+  #[test]
+  fn second_price_with_many_bidders_pays_second_highest_bid() {
+    with_externalities(&mut build_ext(), || {
+      let (kitty_id, auction_id, end_time) = create_kitty_in_auction(ALICE, PricingRule::SecondPrice);
+
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE + 1000));
+      assert_ok!(CatAuction::bid(Origin::signed(CHARLES), auction_id, BASE_PRICE + 4000));
+      assert_ok!(CatAuction::bid(Origin::signed(DAVE), auction_id, BASE_PRICE + 2000));
+      assert_ok!(CatAuction::bid(Origin::signed(EVE), auction_id, BASE_PRICE + 3000));
+      // Bob raises his bid to the top
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE + 6000));
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      assert_ok!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id));
+
+      let auction_tx = CatAuction::auctions(auction_id).tx.unwrap();
+      assert_eq!(auction_tx.winner, BOB);
+      assert_eq!(auction_tx.tx_price, BASE_PRICE + 4000);
+      assert_eq!(CatAuction::kitties(kitty_id).owner, Some(BOB));
+
+      // Bob gets back the part of his reserve above the second price
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(BOB), INI_BALANCE - BASE_PRICE - 4000);
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(BOB), 0);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), INI_BALANCE + BASE_PRICE + 4000);
+      for bidder in [CHARLES, DAVE, EVE].iter() {
+        assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(bidder), INI_BALANCE);
+      }
+    });
+  }
+
+  #[test]
+  fn can_commit_reveal_sealed_bids() {
+    with_externalities(&mut build_ext(), || {
+      let kitty_name_in_hex = KITTY_NAMES[0].as_bytes().to_vec();
+      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name_in_hex, Permill::zero()));
+      let kitty_id = CatAuction::kitty_array(0);
+
+      let deposit = 1000;
+      let end_time = DEFAULT_AUCTION_MIN_DURATION + 5;
+      let reveal_end_time = end_time + 100;
+      assert_ok!(CatAuction::start_sealed_auction(Origin::signed(ALICE), kitty_id, end_time,
+        reveal_end_time, BASE_PRICE, deposit, PricingRule::FirstPrice));
+      let auction_id = CatAuction::auction_array(0);
+
+      // plain bid is not accepted in sealed-bid auction
+      assert_noop!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE),
+        Error::NotEnglishAuction.into());
+
+      let (bob_salt, charles_salt, dave_salt) = (H256::from([1u8; 32]), H256::from([2u8; 32]),
+        H256::from([3u8; 32]));
+      let bob_commitment = CatAuction::bid_commitment(auction_id, &BOB, BASE_PRICE + 2000, bob_salt);
+      assert_ok!(CatAuction::commit_bid(Origin::signed(BOB), auction_id, bob_commitment));
+      assert_ok!(CatAuction::commit_bid(Origin::signed(CHARLES), auction_id,
+        CatAuction::bid_commitment(auction_id, &CHARLES, BASE_PRICE + 5000, charles_salt)));
+      assert_ok!(CatAuction::commit_bid(Origin::signed(DAVE), auction_id,
+        CatAuction::bid_commitment(auction_id, &DAVE, BASE_PRICE + 1000, dave_salt)));
+      // Eve copies Bob's commitment
+      assert_ok!(CatAuction::commit_bid(Origin::signed(EVE), auction_id, bob_commitment));
+      // an account without money cannot lock the deposit
+      assert_eq!(CatAuction::commit_bid(Origin::signed(99), auction_id, bob_commitment),
+        Err(Error::BidDepositUnaffordable.into()));
+
+      // only the deposit is locked, and no price is stored
+      let bob_bid = CatAuction::bids(CatAuction::auction_bidder_bids((auction_id, BOB)));
+      assert_eq!(bob_bid.price, 0);
+      assert_eq!(bob_bid.status, BidStatus::Committed);
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(BOB), deposit);
+
+      assert_noop!(CatAuction::reveal_bid(Origin::signed(BOB), auction_id, BASE_PRICE + 2000, bob_salt),
+        Error::RevealTooEarly.into());
+
+      // reveal window. Dave never reveals his bid
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      assert_noop!(CatAuction::reveal_bid(Origin::signed(BOB), auction_id, BASE_PRICE + 3000, bob_salt),
+        Error::CommitmentMismatch.into());
+      assert_ok!(CatAuction::reveal_bid(Origin::signed(BOB), auction_id, BASE_PRICE + 2000, bob_salt));
+      assert_ok!(CatAuction::reveal_bid(Origin::signed(CHARLES), auction_id, BASE_PRICE + 5000, charles_salt));
+      // Eve cannot replay Bob's revealed price and salt on the copied commitment
+      assert_noop!(CatAuction::reveal_bid(Origin::signed(EVE), auction_id, BASE_PRICE + 2000, bob_salt),
+        Error::CommitmentMismatch.into());
+
+      assert_noop!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id),
+        Error::AuctionNotExpired.into());
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(reveal_end_time);
+      assert_ok!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id));
+
+      let auction_tx = CatAuction::auctions(auction_id).tx.unwrap();
+      assert_eq!(auction_tx.winner, CHARLES);
+      assert_eq!(auction_tx.tx_price, BASE_PRICE + 5000);
+      assert_eq!(CatAuction::kitties(kitty_id).owner, Some(CHARLES));
+
+      // Charles and Bob get their deposit back, Dave and Eve forfeit half of their deposit to Alice
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(CHARLES), INI_BALANCE - BASE_PRICE - 5000);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(BOB), INI_BALANCE);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(DAVE), INI_BALANCE - deposit / 2);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(EVE), INI_BALANCE - deposit / 2);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE),
+        INI_BALANCE + BASE_PRICE + 5000 + deposit);
+      for bidder in [BOB, CHARLES, DAVE, EVE].iter() {
+        assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(bidder), 0);
+      }
+    });
+  }
+
+  #[test]
+  fn expired_auctions_are_settled_on_finalize() {
+    with_externalities(&mut build_ext(), || {
+      let (kitty_id, auction_id, end_time) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+
+      // not expired yet, nothing is settled
+      CatAuction::on_finalize(1);
+      assert_eq!(CatAuction::auctions(auction_id).status, AuctionStatus::Ongoing);
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      CatAuction::on_finalize(2);
+
+      let auction = CatAuction::auctions(auction_id);
+      assert_eq!(auction.status, AuctionStatus::Closed);
+      assert_eq!(auction.tx.unwrap().winner, BOB);
+      assert_eq!(CatAuction::kitties(kitty_id).owner, Some(BOB));
+
+      // closing it again manually is rejected
+      assert_noop!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id),
+        Error::AuctionNotActive.into());
+    });
+  }
+
+  #[test]
+  fn auctions_are_settled_at_their_close_time_whatever_the_start_order() {
+    with_externalities(&mut build_ext(), || {
+      // the auction started first closes last
+      let short_end_time = DEFAULT_AUCTION_MIN_DURATION + 5;
+      let long_end_time = short_end_time + 10 * SCHEDULE_BUCKET_SECS;
+      let mut auction_ids = Vec::new();
+      for end_time in vec![long_end_time, short_end_time] {
+        let kitty_name_in_hex = KITTY_NAMES[0].as_bytes().to_vec();
+        assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name_in_hex, Permill::zero()));
+        let kitty_id = CatAuction::kitty_array(CatAuction::kitties_count() - 1);
+        assert_ok!(CatAuction::start_auction(Origin::signed(ALICE), kitty_id, end_time, BASE_PRICE,
+          PricingRule::FirstPrice, None, None, None));
+        auction_ids.push(CatAuction::auction_array(CatAuction::auctions_count() - 1));
+      }
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(short_end_time);
+      CatAuction::on_finalize(1);
+      assert_eq!(CatAuction::auctions(auction_ids[1]).status, AuctionStatus::Closed);
+      assert_eq!(CatAuction::auctions(auction_ids[0]).status, AuctionStatus::Ongoing);
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(long_end_time);
+      CatAuction::on_finalize(2);
+      assert_eq!(CatAuction::auctions(auction_ids[0]).status, AuctionStatus::Closed);
+    });
+  }
+
+  #[test]
+  fn failed_settlement_leaves_auction_untouched_and_is_retried() {
+    with_externalities(&mut build_ext(), || {
+      let (kitty_id, auction_id, end_time) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+      assert_ok!(CatAuction::bid(Origin::signed(CHARLES), auction_id, BASE_PRICE + 100));
+
+      // the winner loses the money of the bid, so cannot pay
+      <balances::Module<CatAuctionTest>>::slash_reserved(&CHARLES, BASE_PRICE + 100);
+      let charles_free = <balances::Module<CatAuctionTest>>::free_balance(CHARLES);
+      assert_ok!(<balances::Module<CatAuctionTest> as Currency<_>>::transfer(&CHARLES, &EVE, charles_free));
+      let alice_balance = <balances::Module<CatAuctionTest>>::free_balance(ALICE);
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      assert_noop!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id),
+        Error::BuyerCannotPay.into());
+      CatAuction::on_finalize(1);
+      assert_eq!(CatAuction::auctions(auction_id).status, AuctionStatus::Ongoing);
+      assert_eq!(CatAuction::kitties(kitty_id).owner, Some(ALICE));
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), alice_balance);
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(BOB), BASE_PRICE);
+
+      // retried in the next bucket, once the winner can pay again
+      assert_ok!(<balances::Module<CatAuctionTest> as Currency<_>>::transfer(&EVE, &CHARLES, charles_free));
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time + SCHEDULE_BUCKET_SECS);
+      CatAuction::on_finalize(2);
+      assert_eq!(CatAuction::auctions(auction_id).status, AuctionStatus::Closed);
+      assert_eq!(CatAuction::kitties(kitty_id).owner, Some(CHARLES));
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), alice_balance + BASE_PRICE + 100);
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(BOB), 0);
+    });
+  }
+
+  #[test]
+  fn settlement_per_block_is_bounded() {
+    with_externalities(&mut build_ext(), || {
+      let auctions_num = MAX_SETTLEMENTS_PER_BLOCK + 2;
+      let mut end_time = 0;
+      for _ in 0..auctions_num {
+        let (_, _, auction_end_time) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+        end_time = auction_end_time;
+      }
+      let closed_count = || (0..CatAuction::auctions_count())
+        .filter(|i| CatAuction::auctions(CatAuction::auction_array(i)).status == AuctionStatus::Closed)
+        .count();
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      CatAuction::on_finalize(1);
+      assert_eq!(closed_count(), MAX_SETTLEMENTS_PER_BLOCK);
+      // settled entries are removed one by one, the rest stay queued
+      let bucket = end_time / SCHEDULE_BUCKET_SECS;
+      let settled = MAX_SETTLEMENTS_PER_BLOCK as u64;
+      assert_eq!(CatAuction::auction_expiry_cursor(), Some((bucket, settled)));
+      assert!(!<AuctionExpiryQueue<CatAuctionTest>>::exists((bucket, settled - 1)));
+      assert!(<AuctionExpiryQueue<CatAuctionTest>>::exists((bucket, settled)));
+
+      // leftovers are settled in the next block
+      CatAuction::on_finalize(2);
+      assert_eq!(closed_count(), auctions_num);
+      assert_eq!(CatAuction::auction_expiry_cursor(), Some((bucket, auctions_num as u64)));
+    });
+  }
+
+  #[test]
+  fn display_bids_are_refreshed_on_finalize() {
+    with_externalities(&mut build_ext(), || {
+      let (_, auction_id, _) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+      let bob_bid_id = CatAuction::auction_bidder_bids((auction_id, BOB));
+
+      // not due yet
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD / 2);
+      CatAuction::on_finalize(1);
+      assert!(CatAuction::auctions(auction_id).display_bids.is_empty());
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD);
+      CatAuction::on_finalize(2);
+      assert_eq!(CatAuction::auctions(auction_id).display_bids, vec![bob_bid_id]);
+      assert_eq!(CatAuction::next_display_refresh(auction_id), Some(2 * DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD));
+
+      // Charles outbids Bob, but it is not shown until the next refresh
+      assert_ok!(CatAuction::bid(Origin::signed(CHARLES), auction_id, BASE_PRICE + 1000));
+      let charles_bid_id = CatAuction::auction_bidder_bids((auction_id, CHARLES));
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD * 3 / 2);
+      CatAuction::on_finalize(3);
+      assert_eq!(CatAuction::auctions(auction_id).display_bids, vec![bob_bid_id]);
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(2 * DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD);
+      CatAuction::on_finalize(4);
+      assert_eq!(CatAuction::auctions(auction_id).display_bids, vec![charles_bid_id, bob_bid_id]);
+    });
+  }
+
+  #[test]
+  fn root_can_tune_auction_params() {
+    with_externalities(&mut build_ext(), || {
+      assert_eq!(CatAuction::topmost_bids_len(), DEFAULT_TOPMOST_BIDS_LEN);
+      assert!(CatAuction::set_auction_min_duration(Origin::signed(ALICE), 10 * 60).is_err());
+      assert_noop!(CatAuction::set_topmost_bids_len(Origin::ROOT, 0),
+        Error::TopmostBidsLenOutOfRange.into());
+      assert_noop!(CatAuction::set_topmost_bids_len(Origin::ROOT, MAX_TOPMOST_BIDS_LEN + 1),
+        Error::TopmostBidsLenOutOfRange.into());
+      assert_noop!(CatAuction::set_auction_min_duration(Origin::ROOT, 0),
+        Error::AuctionMinDurationOutOfRange.into());
+      assert_noop!(CatAuction::set_auction_min_duration(Origin::ROOT, MIN_AUCTION_MIN_DURATION - 1),
+        Error::AuctionMinDurationOutOfRange.into());
+      assert_noop!(CatAuction::set_auction_min_duration(Origin::ROOT, MAX_AUCTION_MIN_DURATION + 1),
+        Error::AuctionMinDurationOutOfRange.into());
+      assert_noop!(CatAuction::set_auction_min_duration(Origin::ROOT, u64::max_value()),
+        Error::AuctionMinDurationOutOfRange.into());
+      assert_noop!(CatAuction::set_display_bids_update_period(Origin::ROOT, 0),
+        Error::DisplayPeriodOutOfRange.into());
+      assert_noop!(CatAuction::set_display_bids_update_period(Origin::ROOT, MAX_DISPLAY_BIDS_UPDATE_PERIOD + 1),
+        Error::DisplayPeriodOutOfRange.into());
+      assert_eq!(CatAuction::auction_min_duration(), DEFAULT_AUCTION_MIN_DURATION);
+      assert_eq!(CatAuction::display_bids_update_period(), DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD);
+
+      // a longer min duration applies to auctions started afterwards
+      assert_ok!(CatAuction::set_auction_min_duration(Origin::ROOT, 10 * 60));
+      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), KITTY_NAMES[0].as_bytes().to_vec(), Permill::zero()));
+      let kitty_id = CatAuction::kitty_array(0);
+      assert_noop!(CatAuction::start_auction(Origin::signed(ALICE), kitty_id, DEFAULT_AUCTION_MIN_DURATION + 5,
+        BASE_PRICE, PricingRule::FirstPrice, None, None, None), Error::EndTimeTooSoon.into());
+      assert_ok!(CatAuction::start_auction(Origin::signed(ALICE), kitty_id, 10 * 60 + 5,
+        BASE_PRICE, PricingRule::FirstPrice, None, None, None));
+      let auction_id = CatAuction::auction_array(0);
+
+      // fewer topmost bids are kept once root lowers the length
+      assert_ok!(CatAuction::set_topmost_bids_len(Origin::ROOT, 1));
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE + 500));
+      assert_ok!(CatAuction::bid(Origin::signed(CHARLES), auction_id, BASE_PRICE + 1000));
+      let bob_bid_id = CatAuction::auction_bidder_bids((auction_id, BOB));
+      let charles_bid_id = CatAuction::auction_bidder_bids((auction_id, CHARLES));
+      assert_eq!(CatAuction::auctions(auction_id).topmost_bids, vec![charles_bid_id]);
+
+      // and refilled once it is raised again, Bob's dropped bid outranking the new lower one
+      assert_ok!(CatAuction::set_topmost_bids_len(Origin::ROOT, 3));
+      assert_ok!(CatAuction::bid(Origin::signed(DAVE), auction_id, BASE_PRICE + 100));
+      let dave_bid_id = CatAuction::auction_bidder_bids((auction_id, DAVE));
+      assert_eq!(CatAuction::auctions(auction_id).topmost_bids,
+        vec![charles_bid_id, bob_bid_id, dave_bid_id]);
+
+      // display bids can be refreshed on demand after the new period
+      assert_ok!(CatAuction::set_display_bids_update_period(Origin::ROOT, 30));
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(29);
+      assert_noop!(CatAuction::update_auction_display_bids(Origin::INHERENT, auction_id),
+        Error::DisplayBidsRecentlyUpdated.into());
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(30);
+      assert_ok!(CatAuction::update_auction_display_bids(Origin::INHERENT, auction_id));
+      assert_eq!(CatAuction::auctions(auction_id).display_bids,
+        vec![charles_bid_id, bob_bid_id, dave_bid_id]);
+    });
+  }
+
+  #[test]
+  fn display_bids_are_refreshed_at_their_time_whatever_the_queue_order() {
+    with_externalities(&mut build_ext(), || {
+      // the auction queued first is refreshed last
+      assert_ok!(CatAuction::set_display_bids_update_period(Origin::ROOT, 10 * SCHEDULE_BUCKET_SECS));
+      let (_, late_auction_id, _) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+      assert_ok!(CatAuction::set_display_bids_update_period(Origin::ROOT, DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD));
+      let (_, early_auction_id, _) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD);
+      CatAuction::on_finalize(1);
+      assert_eq!(CatAuction::auctions(early_auction_id).display_bids_last_update,
+        DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD);
+      assert_eq!(CatAuction::auctions(late_auction_id).display_bids_last_update, 0);
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(10 * SCHEDULE_BUCKET_SECS);
+      CatAuction::on_finalize(2);
+      assert_eq!(CatAuction::auctions(late_auction_id).display_bids_last_update, 10 * SCHEDULE_BUCKET_SECS);
+    });
+  }
+
+  #[test]
+  fn display_refresh_per_block_is_bounded() {
+    with_externalities(&mut build_ext(), || {
+      let auctions_num = MAX_DISPLAY_REFRESHES_PER_BLOCK + 2;
+      for _ in 0..auctions_num {
+        create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+      }
+      let refreshed_count = || (0..CatAuction::auctions_count())
+        .filter(|i| CatAuction::auctions(CatAuction::auction_array(i)).display_bids_last_update ==
+          DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD)
+        .count();
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(DEFAULT_DISPLAY_BIDS_UPDATE_PERIOD);
+      CatAuction::on_finalize(1);
+      assert_eq!(refreshed_count(), MAX_DISPLAY_REFRESHES_PER_BLOCK);
+
+      // leftovers are refreshed in the next block
+      CatAuction::on_finalize(2);
+      assert_eq!(refreshed_count(), auctions_num);
+    });
+  }
+
+  #[test]
+  fn late_bid_extends_soft_close_auction() {
+    with_externalities(&mut build_ext(), || {
+      let kitty_name_in_hex = KITTY_NAMES[0].as_bytes().to_vec();
+      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name_in_hex, Permill::zero()));
+      let kitty_id = CatAuction::kitty_array(0);
+
+      let end_time = DEFAULT_AUCTION_MIN_DURATION + 5;
+      let soft_close = SoftClose { window: 30, extension: 60, max_end_time: end_time + 100 };
+      assert_ok!(CatAuction::start_auction(Origin::signed(ALICE), kitty_id, end_time, BASE_PRICE,
+        PricingRule::FirstPrice, Some(soft_close), None, None));
+      let auction_id = CatAuction::auction_array(0);
+
+      // bid before the soft close window does not extend the auction
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+      assert_eq!(CatAuction::auctions(auction_id).end_time, end_time);
+
+      // bid inside the window extends the auction
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time - 10);
+      assert_ok!(CatAuction::bid(Origin::signed(CHARLES), auction_id, BASE_PRICE + 1000));
+      assert_eq!(CatAuction::auctions(auction_id).end_time, end_time + 60);
+
+      // the original end time has passed, but the auction is not settled
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      CatAuction::on_finalize(1);
+      assert_eq!(CatAuction::auctions(auction_id).status, AuctionStatus::Ongoing);
+      assert_noop!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id),
+        Error::AuctionNotExpired.into());
+
+      // extension is capped by the max end time
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time + 50);
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE + 2000));
+      assert_eq!(CatAuction::auctions(auction_id).end_time, end_time + 100);
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time + 100);
+      CatAuction::on_finalize(2);
+      let auction = CatAuction::auctions(auction_id);
+      assert_eq!(auction.status, AuctionStatus::Closed);
+      assert_eq!(auction.tx.unwrap().winner, BOB);
+    });
+  }
+
+  #[test]
+  fn bid_has_to_beat_leading_bid_by_min_increment() {
+    with_externalities(&mut build_ext(), || {
+      let end_time = DEFAULT_AUCTION_MIN_DURATION + 5;
+      for kitty_name in KITTY_NAMES.iter() {
+        assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name.as_bytes().to_vec(), Permill::zero()));
+      }
+
+      // absolute increment
+      assert_ok!(CatAuction::start_auction(Origin::signed(ALICE), CatAuction::kitty_array(0),
+        end_time, BASE_PRICE, PricingRule::FirstPrice, None, Some(BidIncrement::Absolute(500)), None));
+      let auction_id = CatAuction::auction_array(0);
+
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+      assert_noop!(CatAuction::bid(Origin::signed(CHARLES), auction_id, BASE_PRICE + 499),
+        Error::BidIncrementTooLow.into());
+      assert_ok!(CatAuction::bid(Origin::signed(CHARLES), auction_id, BASE_PRICE + 500));
+      // raising his own bid still has to beat the leading bid
+      assert_noop!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE + 600),
+        Error::BidIncrementTooLow.into());
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE + 1000));
+
+      // percentage increment of the leading bid
+      assert_ok!(CatAuction::start_auction(Origin::signed(ALICE), CatAuction::kitty_array(1),
+        end_time, BASE_PRICE, PricingRule::FirstPrice, None,
+        Some(BidIncrement::Percentage(Permill::from_percent(10))), None));
+      let auction_id = CatAuction::auction_array(1);
+
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+      assert_noop!(CatAuction::bid(Origin::signed(CHARLES), auction_id, BASE_PRICE + 999),
+        Error::BidIncrementTooLow.into());
+      assert_ok!(CatAuction::bid(Origin::signed(CHARLES), auction_id, BASE_PRICE + 1000));
+
+      // an increment overflowing the balance type rejects further bids
+      assert_ok!(CatAuction::start_auction(Origin::signed(ALICE), CatAuction::kitty_array(2),
+        end_time, BASE_PRICE, PricingRule::FirstPrice, None, Some(BidIncrement::Absolute(u64::max_value())), None));
+      let auction_id = CatAuction::auction_array(2);
+
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+      assert_noop!(CatAuction::bid(Origin::signed(CHARLES), auction_id, BASE_PRICE + 1),
+        Error::BidIncrementOverflow.into());
+    });
+  }
+
+  #[test]
+  fn can_buy_now() {
+    with_externalities(&mut build_ext(), || {
+      let kitty_name_in_hex = KITTY_NAMES[0].as_bytes().to_vec();
+      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name_in_hex, Permill::zero()));
+      let kitty_id = CatAuction::kitty_array(0);
+
+      let buy_now_price = 2 * BASE_PRICE;
+      assert_ok!(CatAuction::start_auction(Origin::signed(ALICE), kitty_id, DEFAULT_AUCTION_MIN_DURATION + 5,
+        BASE_PRICE, PricingRule::FirstPrice, None, None, Some(buy_now_price)));
+      let auction_id = CatAuction::auction_array(0);
+
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+      assert_ok!(CatAuction::bid(Origin::signed(CHARLES), auction_id, BASE_PRICE + 1000));
+      assert_noop!(CatAuction::buy_now(Origin::signed(ALICE), auction_id),
+        Error::OwnerCannotBuy.into());
+
+      // Bob buys it now, with his bid money counted in
+      assert_ok!(CatAuction::buy_now(Origin::signed(BOB), auction_id));
+
+      let auction = CatAuction::auctions(auction_id);
+      assert_eq!(auction.status, AuctionStatus::Closed);
+      let auction_tx = auction.tx.unwrap();
+      assert_eq!(auction_tx.winner, BOB);
+      assert_eq!(auction_tx.tx_price, buy_now_price);
+
+      let kitty = CatAuction::kitties(kitty_id);
+      assert_eq!(kitty.owner, Some(BOB));
+      assert!(!kitty.in_auction);
+
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), INI_BALANCE + buy_now_price);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(BOB), INI_BALANCE - buy_now_price);
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(BOB), 0);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(CHARLES), INI_BALANCE);
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(CHARLES), 0);
+
+      assert_noop!(CatAuction::buy_now(Origin::signed(DAVE), auction_id), Error::AuctionNotActive.into());
+    });
+  }
+
+  #[test]
+  fn cannot_buy_now_once_leading_bid_reaches_the_price() {
+    with_externalities(&mut build_ext(), || {
+      let kitty_name_in_hex = KITTY_NAMES[0].as_bytes().to_vec();
+      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name_in_hex, Permill::zero()));
+      let kitty_id = CatAuction::kitty_array(0);
+
+      let buy_now_price = 2 * BASE_PRICE;
+      assert_ok!(CatAuction::start_auction(Origin::signed(ALICE), kitty_id, DEFAULT_AUCTION_MIN_DURATION + 5,
+        BASE_PRICE, PricingRule::FirstPrice, None, None, Some(buy_now_price)));
+      let auction_id = CatAuction::auction_array(0);
+
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, buy_now_price));
+      assert_noop!(CatAuction::buy_now(Origin::signed(CHARLES), auction_id), Error::BuyNowOutbid.into());
+      assert_noop!(CatAuction::buy_now(Origin::signed(BOB), auction_id), Error::BuyNowOutbid.into());
+      assert_eq!(CatAuction::auctions(auction_id).status, AuctionStatus::Ongoing);
+    });
+  }
+
+  #[test]
+  fn can_accept_dutch_auction_price() {
+    with_externalities(&mut build_ext(), || {
+      for kitty_name in KITTY_NAMES[0..2].iter() {
+        assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name.as_bytes().to_vec(), Permill::zero()));
+      }
+      let end_time = 200;
+      let start_price = 2 * BASE_PRICE;
+
+      // linear price fall
+      assert_ok!(CatAuction::start_dutch_auction(Origin::signed(ALICE), CatAuction::kitty_array(0),
+        end_time, start_price, BASE_PRICE, 0));
+      // price falls every 60s
+      assert_ok!(CatAuction::start_dutch_auction(Origin::signed(ALICE), CatAuction::kitty_array(1),
+        end_time, start_price, BASE_PRICE, 60));
+      let (linear_auction_id, step_auction_id) = (CatAuction::auction_array(0), CatAuction::auction_array(1));
+
+      assert_noop!(CatAuction::bid(Origin::signed(BOB), linear_auction_id, start_price),
+        Error::NotEnglishAuction.into());
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time / 2);
+      assert_ok!(CatAuction::accept_price(Origin::signed(BOB), linear_auction_id));
+      assert_ok!(CatAuction::accept_price(Origin::signed(CHARLES), step_auction_id));
+
+      // linear: halfway through, the price is halfway between start and floor price
+      let auction_tx = CatAuction::auctions(linear_auction_id).tx.unwrap();
+      assert_eq!(auction_tx.winner, BOB);
+      assert_eq!(auction_tx.tx_price, BASE_PRICE + BASE_PRICE / 2);
+      assert_eq!(CatAuction::kitties(CatAuction::kitty_array(0)).owner, Some(BOB));
+
+      // step: only one step of 60s has passed
+      let auction_tx = CatAuction::auctions(step_auction_id).tx.unwrap();
+      assert_eq!(auction_tx.winner, CHARLES);
+      assert_eq!(auction_tx.tx_price, start_price - BASE_PRICE * 60 / end_time);
+      assert_eq!(CatAuction::kitties(CatAuction::kitty_array(1)).owner, Some(CHARLES));
+
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE),
+        INI_BALANCE + BASE_PRICE + BASE_PRICE / 2 + start_price - BASE_PRICE * 60 / end_time);
+      assert_noop!(CatAuction::accept_price(Origin::signed(DAVE), linear_auction_id),
+        Error::AuctionNotActive.into());
+    });
+  }
+
+  // check OwnedKitties, OwnerKittiesCount, and kitty owner of `owner` are consistent, walking
+  //   the owner kitty list both forward and backward
+  fn assert_owner_kitties(owner: u64, kitty_ids: &[H256]) {
+    assert_eq!(CatAuction::owner_kitties_count(owner), kitty_ids.len() as u64);
+    assert_eq!(CatAuction::owned_kitty_ids(&owner), kitty_ids.to_vec());
+
+    let mut prev = CatAuction::_owned_kitties_item(&owner, None).prev;
+    for kitty_id in kitty_ids.iter().rev() {
+      assert_eq!(prev, Some(*kitty_id));
+      assert_eq!(CatAuction::kitties(kitty_id).owner, Some(owner));
+      prev = CatAuction::_owned_kitties_item(&owner, prev).prev;
+    }
+    assert_eq!(prev, None);
+  }
+
+  #[test]
+  fn can_transfer_kitty_among_owners_with_several_kitties() {
+    with_externalities(&mut build_ext(), || {
+      for kitty_name in KITTY_NAMES.iter() {
+        assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name.as_bytes().to_vec(), Permill::zero()));
+      }
+      assert_ok!(CatAuction::create_kitty(Origin::signed(BOB), KITTY_NAMES[0].as_bytes().to_vec(), Permill::zero()));
+      let (k0, k1, k2, k3) = (CatAuction::kitty_array(0), CatAuction::kitty_array(1),
+        CatAuction::kitty_array(2), CatAuction::kitty_array(3));
+      assert_owner_kitties(ALICE, &[k0, k1, k2]);
+      assert_owner_kitties(BOB, &[k3]);
+
+      assert_noop!(CatAuction::transfer_kitty(Origin::signed(BOB), CHARLES, k0),
+        "You are not the kitty owner");
+
+      // transferring the first kitty, the rest keep their order
+      assert_ok!(CatAuction::transfer_kitty(Origin::signed(ALICE), BOB, k0));
+      assert_owner_kitties(ALICE, &[k1, k2]);
+      assert_owner_kitties(BOB, &[k3, k0]);
+
+      // transferring the first kitty again
+      assert_ok!(CatAuction::transfer_kitty(Origin::signed(ALICE), BOB, k1));
+      assert_owner_kitties(ALICE, &[k2]);
+      assert_owner_kitties(BOB, &[k3, k0, k1]);
+
+      // kitty in auction cannot be transferred
+      let end_time = DEFAULT_AUCTION_MIN_DURATION + 5;
+      assert_ok!(CatAuction::start_auction(Origin::signed(BOB), k3, end_time, BASE_PRICE,
+        PricingRule::FirstPrice, None, None, None));
+      assert_noop!(CatAuction::transfer_kitty(Origin::signed(BOB), CHARLES, k3),
+        "Kitty is in an auction");
+
+      // Alice wins Bob first kitty in auction
+      let auction_id = CatAuction::auction_array(0);
+      assert_ok!(CatAuction::bid(Origin::signed(ALICE), auction_id, BASE_PRICE));
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      assert_ok!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id));
+      assert_owner_kitties(ALICE, &[k2, k3]);
+      assert_owner_kitties(BOB, &[k0, k1]);
+    });
+  }
+
+  #[test]
+  fn owned_kitties_stay_consistent_across_many_transfers() {
+    with_externalities(&mut build_ext(), || {
+      let accounts = [ALICE, BOB, CHARLES];
+      let mut owned: Vec<Vec<H256>> = vec![Vec::new(); accounts.len()];
+      for i in 0..9 {
+        let owner = i % accounts.len();
+        assert_ok!(CatAuction::create_kitty(Origin::signed(accounts[owner]),
+          KITTY_NAMES[i % KITTY_NAMES.len()].as_bytes().to_vec(), Permill::zero()));
+        owned[owner].push(CatAuction::kitty_array(i as u64));
+      }
+
+      // move kitties from the front, middle and back of the lists around, checking the
+      //   lists against a plain model after every transfer
+      for round in 0..30 {
+        let from = round % accounts.len();
+        let to = (from + 1 + (round / accounts.len()) % 2) % accounts.len();
+        if owned[from].is_empty() { continue; }
+
+        let pos = (round * 7) % owned[from].len();
+        let kitty_id = owned[from].remove(pos);
+        assert_ok!(CatAuction::transfer_kitty(Origin::signed(accounts[from]), accounts[to], kitty_id));
+        owned[to].push(kitty_id);
+
+        for (account, kitty_ids) in accounts.iter().zip(owned.iter()) {
+          assert_owner_kitties(*account, kitty_ids);
+        }
+      }
+    });
+  }
+
+  // store a kitty in storage v1 layout, indexed at `pos` of the owner kitties
+  // storage items of the module before storage versioning, declared as the baseline did, so
+  //   values are written under the keys a v1 chain has them. Values are the baseline structs,
+  //   field by field
+  mod v1 {
+    use super::*;
+
+    pub type KittyOf<T> = (<T as system::Trait>::Hash, Option<Vec<u8>>,
+      Option<<T as system::Trait>::AccountId>, Option<u64>, bool);
+    pub type AuctionTxOf<T> = (<T as timestamp::Trait>::Moment, <T as system::Trait>::AccountId,
+      <T as balances::Trait>::Balance);
+    pub type AuctionOf<T> = (<T as system::Trait>::Hash, <T as system::Trait>::Hash,
+      <T as balances::Trait>::Balance, <T as timestamp::Trait>::Moment,
+      <T as timestamp::Trait>::Moment, AuctionStatus, Vec<<T as system::Trait>::Hash>,
+      <T as balances::Trait>::Balance, Vec<<T as system::Trait>::Hash>,
+      <T as timestamp::Trait>::Moment, Option<AuctionTxOf<T>>);
+    pub type BidOf<T> = (<T as system::Trait>::Hash, <T as system::Trait>::Hash,
+      <T as system::Trait>::AccountId, <T as balances::Trait>::Balance,
+      <T as timestamp::Trait>::Moment, BidStatus);
+
+    pub struct Module<T: Trait>(rstd::marker::PhantomData<T>);
+
+    decl_storage! {
+      trait Store for Module<T: Trait> as CatAuction {
+        pub Kitties: map T::Hash => KittyOf<T>;
+        pub OwnerKitties: map (T::AccountId, u64) => T::Hash;
+        pub Auctions: map T::Hash => AuctionOf<T>;
+        pub Bids: map T::Hash => BidOf<T>;
+      }
+    }
+  }
+
+  fn put_kitty_v1(index: u64, owner: u64, pos: u64) -> H256 {
+    let kitty_id = H256::from([(index + 1) as u8; 32]);
+    let name = KITTY_NAMES[(index % 3) as usize].as_bytes().to_vec();
+    <v1::Kitties<CatAuctionTest>>::insert(kitty_id, (kitty_id, Some(name), Some(owner), Some(pos), false));
+    <v1::OwnerKitties<CatAuctionTest>>::insert((owner, pos), kitty_id);
+    <KittiesArray<CatAuctionTest>>::insert(index, kitty_id);
+    <KittiesCount<CatAuctionTest>>::put(index + 1);
+    <OwnerKittiesCount<CatAuctionTest>>::mutate(owner, |cnt| *cnt += 1);
+    kitty_id
+  }
+
+  // an ongoing v1 auction of the kitty, with a bid of each bidder at the given price. The bid
+  //   prices are reserved, as v1 does. Returns the auction ID and the bid IDs
+  fn put_auction_v1(kitty_id: H256, end_time: u64, bids: Vec<(u64, u64)>) -> (H256, Vec<H256>) {
+    let auction_id = H256::from([0xa0; 32]);
+    let mut bid_ids = Vec::new();
+    for (i, (bidder, price)) in bids.iter().enumerate() {
+      let bid_id = H256::from([0xb0 + i as u8; 32]);
+      <v1::Bids<CatAuctionTest>>::insert(bid_id, (bid_id, auction_id, *bidder, *price, 0, BidStatus::Active));
+      assert_ok!(<balances::Module<CatAuctionTest>>::reserve(bidder, *price));
+      <AuctionBids<CatAuctionTest>>::insert((auction_id, i as u64), bid_id);
+      <AuctionBidderBids<CatAuctionTest>>::insert((auction_id, *bidder), bid_id);
+      bid_ids.push(bid_id);
+    }
+    <AuctionBidsCount<CatAuctionTest>>::insert(auction_id, bids.len() as u64);
+
+    // bids are sorted by price for the topmost bids
+    let mut topmost_bids: Vec<(u64, H256)> = bids.iter().map(|bid| bid.1).zip(bid_ids.clone()).collect();
+    topmost_bids.sort_by(|a, b| b.0.cmp(&a.0));
+    let topmost_bids: Vec<H256> = topmost_bids.into_iter().map(|bid| bid.1).collect();
+    <v1::Auctions<CatAuctionTest>>::insert(auction_id, (auction_id, kitty_id, BASE_PRICE, 0, end_time,
+      AuctionStatus::Ongoing, topmost_bids.clone(), BASE_PRICE, topmost_bids, 0, None));
+    <AuctionsArray<CatAuctionTest>>::insert(0, auction_id);
+    <AuctionsCount<CatAuctionTest>>::put(1);
+    (auction_id, bid_ids)
+  }
+
+  #[test]
+  fn v1_values_decode_as_v2_after_upgrade() {
+    let kitty_id = H256::from([1; 32]);
+    let encoded_v1 = (kitty_id, Some(KITTY_NAMES[0].as_bytes().to_vec()), Some(ALICE), Some(3u64), true).encode();
+    // v1 bytes are not a v2 kitty as is
+    assert!(Kitty::<H256, u64, u64>::decode(&mut &encoded_v1[..]).is_none());
+
+    let (kitty, owner_pos) = KittyV1::<H256, u64>::decode(&mut &encoded_v1[..]).unwrap().upgrade::<u64>();
+    assert_eq!(owner_pos, Some(3));
+    assert_eq!(Kitty::<H256, u64, u64>::decode(&mut &kitty.encode()[..]), Some(kitty.clone()));
+    assert_eq!((kitty.id, kitty.owner, kitty.in_auction, kitty.dna), (kitty_id, Some(ALICE), true, kitty_id));
+    assert_eq!((kitty.parents, kitty.generation, kitty.creator, kitty.royalty),
+      (None, 0, None, Permill::zero()));
+
+    let (auction_id, bid_id) = (H256::from([2; 32]), H256::from([3; 32]));
+    let encoded_v1 = (auction_id, kitty_id, BASE_PRICE, 10u64, 200u64, AuctionStatus::Closed,
+      vec![bid_id], BASE_PRICE + 1, vec![bid_id], 20u64, Some((200u64, BOB, BASE_PRICE + 1))).encode();
+    let auction = AuctionV1::<H256, u64, u64, AuctionTxV1<u64, u64, u64>>::decode(&mut &encoded_v1[..])
+      .unwrap().upgrade();
+    assert_eq!(Auction::<H256, u64, u64, AuctionTx<u64, u64, u64>>::decode(&mut &auction.encode()[..]),
+      Some(auction.clone()));
+    assert_eq!((auction.kitty_id, auction.end_time, auction.status), (kitty_id, 200, AuctionStatus::Closed));
+    assert_eq!((auction.kind, auction.pricing_rule, auction.item),
+      (AuctionKind::English, PricingRule::FirstPrice, AuctionItem::Ownership));
+    assert_eq!((auction.topmost_bids, auction.display_bids_last_update), (vec![bid_id], 20));
+    let tx = auction.tx.unwrap();
+    assert_eq!((tx.tx_time, tx.winner, tx.tx_price, tx.fee, tx.royalty), (200, BOB, BASE_PRICE + 1, 0, 0));
+
+    let encoded_v1 = (bid_id, auction_id, BOB, BASE_PRICE + 1, 30u64, BidStatus::Active).encode();
+    let bid = BidV1::<H256, u64, u64, u64>::decode(&mut &encoded_v1[..]).unwrap().upgrade();
+    assert_eq!(Bid::<H256, u64, u64, u64>::decode(&mut &bid.encode()[..]), Some(bid.clone()));
+    assert_eq!((bid.auction_id, bid.bidder, bid.price, bid.status), (auction_id, BOB, BASE_PRICE + 1, BidStatus::Active));
+    assert_eq!((bid.commitment, bid.deposit), (None, 0));
+  }
+
+  #[test]
+  fn storage_v1_is_migrated_in_bounded_batches_on_initialize() {
+    with_externalities(&mut build_ext(), || {
+      // the chain predates storage versioning, unlike the test genesis
+      <StorageVersion<CatAuctionTest>>::kill();
+      // kitties stored in v1 layout, alternately owned by Alice and Bob
+      let kitties_count = MAX_MIGRATIONS_PER_BLOCK + 2;
+      let mut owned: Vec<Vec<H256>> = vec![Vec::new(), Vec::new()];
+      for i in 0..kitties_count {
+        let owner = (i % 2) as usize;
+        let kitty_id = put_kitty_v1(i, [ALICE, BOB][owner], owned[owner].len() as u64);
+        owned[owner].push(kitty_id);
+      }
+      // and an auction of an Alice kitty, with bids of Bob and Charles
+      let end_time = 1000;
+      let (auction_id, bid_ids) = put_auction_v1(owned[0][0], end_time,
+        vec![(BOB, BASE_PRICE), (CHARLES, BASE_PRICE + 100)]);
+      assert_eq!(CatAuction::storage_version(), 0);
+
+      // the first batch only gets through kitties
+      CatAuction::on_initialize(1);
+      assert_eq!(CatAuction::storage_version(), 0);
+      assert_eq!(CatAuction::migration_cursor(), (MAX_MIGRATIONS_PER_BLOCK, 0));
+      assert_eq!(CatAuction::kitties(owned[0][0]).owner, Some(ALICE));
+
+      // calls and scheduled work wait for the rest
+      assert_noop!(CatAuction::transfer_kitty(Origin::signed(ALICE), BOB, owned[0][0]),
+        Error::StorageMigrating.into());
+      assert_noop!(CatAuction::close_auction_and_tx(Origin::signed(DAVE), auction_id),
+        Error::StorageMigrating.into());
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      CatAuction::on_finalize(1);
+
+      // the rest: two kitties, then the bids and the auction
+      CatAuction::on_initialize(2);
+      assert_eq!(CatAuction::storage_version(), STORAGE_VERSION);
+      assert_eq!(CatAuction::migration_cursor(), (0, 0));
+      assert_owner_kitties(ALICE, &owned[0]);
+      assert_owner_kitties(BOB, &owned[1]);
+      let last_kitty_id = owned[1][owned[1].len() - 1];
+      assert_eq!(CatAuction::kitties(last_kitty_id).dna, last_kitty_id);
+      assert!(!<v1::OwnerKitties<CatAuctionTest>>::exists((ALICE, 0)));
+      assert_eq!(CatAuction::bids(bid_ids[1]).bidder, CHARLES);
+      assert_eq!(CatAuction::auctions(auction_id).topmost_bids, vec![bid_ids[1], bid_ids[0]]);
+
+      // the ongoing auction is queued for settlement once migrated
+      let alice_balance = <balances::Module<CatAuctionTest>>::free_balance(ALICE);
+      CatAuction::on_finalize(2);
+      assert_eq!(CatAuction::auctions(auction_id).status, AuctionStatus::Closed);
+      assert_eq!(CatAuction::kitties(owned[0][0]).owner, Some(CHARLES));
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), alice_balance + BASE_PRICE + 100);
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(BOB), 0);
+
+      // nothing runs again once at the current version
+      assert_ok!(CatAuction::transfer_kitty(Origin::signed(ALICE), BOB, owned[0][1]));
+      CatAuction::on_initialize(3);
+      assert_eq!(CatAuction::kitties(owned[0][1]).owner, Some(BOB));
+      assert_eq!(CatAuction::owner_kitties_count(BOB), owned[1].len() as u64 + 1);
+    });
+  }
+
+  #[test]
+  fn new_chain_gets_current_storage_version() {
+    with_externalities(&mut build_ext(), || {
+      CatAuction::on_initialize(1);
+      assert_eq!(CatAuction::storage_version(), STORAGE_VERSION);
+    });
+  }
+
+  #[test]
+  fn genesis_kitties_are_minted_in_current_layout() {
+    let dna = H256::from([7; 32]);
+    let mut t = build_ext_with_kitties(vec![
+      (ALICE, KITTY_NAMES[0].as_bytes().to_vec(), dna),
+      (BOB, KITTY_NAMES[1].as_bytes().to_vec(), H256::from([8; 32])),
+      (ALICE, KITTY_NAMES[2].as_bytes().to_vec(), H256::from([9; 32])),
+    ]);
+
+    with_externalities(&mut t, || {
+      assert_eq!(CatAuction::storage_version(), STORAGE_VERSION);
+      CatAuction::on_initialize(1);
+      assert_eq!(CatAuction::kitties_count(), 3);
+
+      let kitty = CatAuction::kitties(CatAuction::kitty_array(0));
+      assert_eq!(kitty.owner, Some(ALICE));
+      assert_eq!(kitty.dna, dna);
+      assert_eq!(kitty.traits, KittyTraits::from_dna(dna.as_ref()));
+      assert_eq!(kitty.name, Some(KITTY_NAMES[0].as_bytes().to_vec()));
+
+      assert_eq!(CatAuction::owner_kitties_count(ALICE), 2);
+      assert_eq!(CatAuction::owned_kitty_ids(&ALICE),
+        vec![CatAuction::kitty_array(0), CatAuction::kitty_array(2)]);
+      assert_eq!(CatAuction::owned_kitty_ids(&BOB), vec![CatAuction::kitty_array(1)]);
+    });
+  }
+
+  #[test]
+  fn can_buy_kitty_at_fixed_price() {
+    with_externalities(&mut build_ext(), || {
+      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), KITTY_NAMES[0].as_bytes().to_vec(), Permill::zero()));
+      let kitty_id = CatAuction::kitty_array(0);
+
+      assert_noop!(CatAuction::buy_kitty(Origin::signed(BOB), kitty_id, BASE_PRICE),
+        "Kitty is not for sale");
+      assert_noop!(CatAuction::set_price(Origin::signed(BOB), kitty_id, Some(BASE_PRICE)),
+        "You are not the kitty owner");
+      assert_ok!(CatAuction::set_price(Origin::signed(ALICE), kitty_id, Some(BASE_PRICE)));
+      assert_eq!(CatAuction::kitty_price(kitty_id), Some(BASE_PRICE));
+
+      // a listed kitty cannot be put in auction
+      let end_time = DEFAULT_AUCTION_MIN_DURATION + 5;
+      assert_noop!(CatAuction::start_auction(Origin::signed(ALICE), kitty_id, end_time, BASE_PRICE,
+        PricingRule::FirstPrice, None, None, None), Error::KittyListed.into());
+
+      // the seller raises the price, the buyer is protected by the max price
+      assert_ok!(CatAuction::set_price(Origin::signed(ALICE), kitty_id, Some(BASE_PRICE * 2)));
+      assert_noop!(CatAuction::buy_kitty(Origin::signed(BOB), kitty_id, BASE_PRICE),
+        "Kitty price is higher than the max price you offered");
+
+      assert_ok!(CatAuction::buy_kitty(Origin::signed(BOB), kitty_id, BASE_PRICE * 2));
+      assert_owner_kitties(ALICE, &[]);
+      assert_owner_kitties(BOB, &[kitty_id]);
+      assert_eq!(CatAuction::kitty_price(kitty_id), None);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), INI_BALANCE + BASE_PRICE * 2);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(BOB), INI_BALANCE - BASE_PRICE * 2);
+
+      // a kitty in auction cannot be listed
+      assert_ok!(CatAuction::start_auction(Origin::signed(BOB), kitty_id, end_time, BASE_PRICE,
+        PricingRule::FirstPrice, None, None, None));
+      assert_noop!(CatAuction::set_price(Origin::signed(BOB), kitty_id, Some(BASE_PRICE)),
+        "Kitty is in an auction");
+    });
+  }
+
+  #[test]
+  fn can_make_n_accept_offers() {
+    with_externalities(&mut build_ext(), || {
+      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), KITTY_NAMES[0].as_bytes().to_vec(), Permill::zero()));
+      let kitty_id = CatAuction::kitty_array(0);
+      let expiry = 100;
+
+      assert_noop!(CatAuction::make_offer(Origin::signed(ALICE), kitty_id, BASE_PRICE, expiry),
+        "You cannot make an offer on your own kitty");
+      assert_ok!(CatAuction::make_offer(Origin::signed(BOB), kitty_id, BASE_PRICE, expiry));
+      assert_ok!(CatAuction::make_offer(Origin::signed(CHARLES), kitty_id, BASE_PRICE + 1000, expiry));
+      assert_ok!(CatAuction::make_offer(Origin::signed(DAVE), kitty_id, BASE_PRICE + 2000, expiry));
+      let (bob_offer, charles_offer, dave_offer) = (CatAuction::kitty_offers((kitty_id, 0)),
+        CatAuction::kitty_offers((kitty_id, 1)), CatAuction::kitty_offers((kitty_id, 2)));
+      assert_eq!(CatAuction::offerer_offers((BOB, 0)), bob_offer);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(BOB), INI_BALANCE - BASE_PRICE);
+
+      // Bob cancels his offer, the last offer takes its position
+      assert_noop!(CatAuction::cancel_offer(Origin::signed(CHARLES), bob_offer), "You are not the offerer");
+      assert_ok!(CatAuction::cancel_offer(Origin::signed(BOB), bob_offer));
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(BOB), INI_BALANCE);
+      assert_eq!(CatAuction::kitty_offers_count(kitty_id), 2);
+      assert_eq!(CatAuction::kitty_offers((kitty_id, 0)), dave_offer);
+      assert_eq!(CatAuction::offerer_offers_count(BOB), 0);
+
+      // Alice accepts Charles offer
+      assert_noop!(CatAuction::accept_offer(Origin::signed(BOB), charles_offer), "You are not the kitty owner");
+      assert_ok!(CatAuction::accept_offer(Origin::signed(ALICE), charles_offer));
+      assert_owner_kitties(ALICE, &[]);
+      assert_owner_kitties(CHARLES, &[kitty_id]);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), INI_BALANCE + BASE_PRICE + 1000);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(CHARLES), INI_BALANCE - BASE_PRICE - 1000);
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(CHARLES), 0);
+
+      // Dave offer expires, and anyone can clear it
+      assert_noop!(CatAuction::clear_expired_offer(Origin::signed(EVE), dave_offer), "Offer has not expired yet");
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(expiry);
+      assert_noop!(CatAuction::accept_offer(Origin::signed(CHARLES), dave_offer), "Offer has expired already");
+      assert_ok!(CatAuction::clear_expired_offer(Origin::signed(EVE), dave_offer));
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(DAVE), INI_BALANCE);
+      assert_eq!(CatAuction::kitty_offers_count(kitty_id), 0);
+      assert_eq!(CatAuction::offerer_offers_count(DAVE), 0);
+    });
+  }
+
+  #[test]
+  fn creator_gets_royalty_on_secondary_sales() {
+    with_externalities(&mut build_ext(), || {
+      assert_noop!(CatAuction::create_kitty(Origin::signed(ALICE), KITTY_NAMES[0].as_bytes().to_vec(),
+        Permill::from_percent(51)), "Royalty cannot be set higher than the max royalty percent");
+      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), KITTY_NAMES[0].as_bytes().to_vec(),
+        Permill::from_percent(10)));
+      let kitty_id = CatAuction::kitty_array(0);
+
+      // the first sale by the creator pays no royalty
+      assert_ok!(CatAuction::set_price(Origin::signed(ALICE), kitty_id, Some(BASE_PRICE)));
+      assert_ok!(CatAuction::buy_kitty(Origin::signed(BOB), kitty_id, BASE_PRICE));
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), INI_BALANCE + BASE_PRICE);
+
+      // Bob sells it in auction, Alice takes 10% of it
+      let end_time = DEFAULT_AUCTION_MIN_DURATION + 5;
+      assert_ok!(CatAuction::start_auction(Origin::signed(BOB), kitty_id, end_time, BASE_PRICE,
+        PricingRule::FirstPrice, None, None, None));
+      let auction_id = CatAuction::auction_array(0);
+      assert_ok!(CatAuction::bid(Origin::signed(CHARLES), auction_id, BASE_PRICE * 2));
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      assert_ok!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id));
+
+      let auction_tx = CatAuction::auctions(auction_id).tx.unwrap();
+      assert_eq!(auction_tx.tx_price, BASE_PRICE * 2);
+      assert_eq!(auction_tx.royalty, BASE_PRICE * 2 / 10);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), INI_BALANCE + BASE_PRICE * 12 / 10);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(BOB), INI_BALANCE + BASE_PRICE * 8 / 10);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(CHARLES), INI_BALANCE - BASE_PRICE * 2);
+
+      // Charles sells it to Dave on an offer, Alice takes 10% from the reserved offer
+      assert_ok!(CatAuction::make_offer(Origin::signed(DAVE), kitty_id, BASE_PRICE, end_time + 100));
+      assert_ok!(CatAuction::accept_offer(Origin::signed(CHARLES), CatAuction::kitty_offers((kitty_id, 0))));
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), INI_BALANCE + BASE_PRICE * 13 / 10);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(CHARLES), INI_BALANCE - BASE_PRICE * 11 / 10);
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(DAVE), 0);
+    });
+  }
+
+  #[test]
+  fn royalty_the_creator_cannot_receive_is_left_to_seller() {
+    with_externalities(&mut build_ext(), || {
+      // the creator account has no balance, so reserved offer money cannot be moved to it
+      let creator = 99;
+      assert_ok!(CatAuction::create_kitty(Origin::signed(creator), KITTY_NAMES[0].as_bytes().to_vec(),
+        Permill::from_percent(10)));
+      let kitty_id = CatAuction::kitty_array(0);
+      assert_ok!(CatAuction::transfer_kitty(Origin::signed(creator), BOB, kitty_id));
+
+      assert_ok!(CatAuction::make_offer(Origin::signed(CHARLES), kitty_id, BASE_PRICE, 100));
+      assert_ok!(CatAuction::accept_offer(Origin::signed(BOB), CatAuction::kitty_offers((kitty_id, 0))));
+
+      assert_eq!(CatAuction::kitties(kitty_id).owner, Some(CHARLES));
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(BOB), INI_BALANCE + BASE_PRICE);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(CHARLES), INI_BALANCE - BASE_PRICE);
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(CHARLES), 0);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(creator), 0);
+    });
+  }
+
+  #[test]
+  fn treasury_collects_protocol_fee() {
+    with_externalities(&mut build_ext(), || {
+      assert!(CatAuction::set_protocol_fee(Origin::signed(ALICE), Permill::from_percent(5)).is_err());
+      assert_ok!(CatAuction::set_protocol_fee(Origin::ROOT, Permill::from_percent(5)));
+      assert_eq!(CatAuction::protocol_fee(), Permill::from_percent(5));
+
+      let (_, auction_id, end_time) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      assert_ok!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id));
+
+      let fee = BASE_PRICE * 5 / 100;
+      assert_eq!(CatAuction::auctions(auction_id).tx.unwrap().fee, fee);
+      assert_eq!(CatAuction::treasury_revenue(), fee);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(CatAuction::treasury_account()), fee);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), INI_BALANCE + BASE_PRICE - fee);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(BOB), INI_BALANCE - BASE_PRICE);
+    });
+  }
+
+  #[test]
+  fn listing_deposit_is_returned_on_close_n_slashed_on_cancel() {
+    with_externalities(&mut build_ext(), || {
+      let deposit = 1000;
+      assert!(CatAuction::set_listing_deposit(Origin::signed(ALICE), deposit).is_err());
+      assert_ok!(CatAuction::set_listing_deposit(Origin::ROOT, deposit));
+
+      // a cancelled auction loses half of its listing deposit to the treasury
+      let (_, auction_id, _) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+      assert_eq!(CatAuction::auctions(auction_id).listing_deposit, deposit);
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(ALICE), deposit);
+      assert_ok!(CatAuction::cancel_auction(Origin::signed(ALICE), auction_id));
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(ALICE), 0);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), INI_BALANCE - deposit / 2);
+      assert_eq!(CatAuction::treasury_slashes(), deposit / 2);
+      assert_eq!(CatAuction::treasury_revenue(), 0);
+
+      // a closed auction returns the listing deposit
+      let (_, auction_id, end_time) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      assert_ok!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id));
+      assert_eq!(<balances::Module<CatAuctionTest>>::reserved_balance(ALICE), 0);
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE),
+        INI_BALANCE - deposit / 2 + BASE_PRICE);
+    });
+  }
+
+  #[test]
+  fn can_query_kitties_auctions_n_bids() {
+    with_externalities(&mut build_ext(), || {
+      let (k0, a0, _) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+      let (k1, a1, _) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+      let (_, a2, _) = create_kitty_in_auction(ALICE, PricingRule::FirstPrice);
+      assert_ok!(CatAuction::cancel_auction(Origin::signed(ALICE), a1));
+
+      let kitty_ids: Vec<H256> = CatAuction::kitties_of(&ALICE, 0, 10).iter().map(|k| k.id).collect();
+      assert_eq!(kitty_ids.len(), 3);
+      assert_eq!(kitty_ids[0..2], [k0, k1]);
+      assert_eq!(CatAuction::kitties_of(&ALICE, 1, 1).len(), 1);
+      assert_eq!(CatAuction::kitties_of(&ALICE, 1, 1)[0].id, k1);
+
+      // ongoing auctions are paged over, skipping the cancelled one
+      let auction_ids: Vec<H256> = CatAuction::ongoing_auctions(0, 10).iter().map(|a| a.id).collect();
+      assert_eq!(auction_ids, vec![a0, a2]);
+      assert_eq!(CatAuction::ongoing_auctions(1, 10)[0].id, a2);
+      assert!(CatAuction::ongoing_auctions(2, 10).is_empty());
+
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), a0, BASE_PRICE));
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), a2, BASE_PRICE + 10));
+      let bid_prices: Vec<u64> = CatAuction::bids_of(&BOB, 0, 10).iter().map(|b| b.price).collect();
+      assert_eq!(bid_prices, vec![BASE_PRICE, BASE_PRICE + 10]);
+      assert!(CatAuction::bids_of(&CHARLES, 0, 10).is_empty());
+
+      // a withdrawn bid leaves the bidder's index
+      assert_ok!(CatAuction::bid(Origin::signed(CHARLES), a0, BASE_PRICE + 100));
+      assert_ok!(CatAuction::withdraw_bid(Origin::signed(BOB), a0));
+      let bid_prices: Vec<u64> = CatAuction::bids_of(&BOB, 0, 10).iter().map(|b| b.price).collect();
+      assert_eq!(bid_prices, vec![BASE_PRICE + 10]);
+
+      // the bid is not displayed until the display bids are refreshed
+      let detail = CatAuction::auction_detail(a0).unwrap();
+      assert_eq!(detail.kitty.id, k0);
+      assert_eq!(detail.bids_count, 1);
+      assert!(detail.display_bids.is_empty());
+      assert_eq!(CatAuction::auction_detail(H256::from([9; 32])), None);
+    });
+  }
+
+  #[test]
+  fn can_breed_kitties() {
+    with_externalities(&mut build_ext(), || {
+      for kitty_name in KITTY_NAMES[0..2].iter() {
+        assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), kitty_name.as_bytes().to_vec(), Permill::zero()));
+      }
+      assert_ok!(CatAuction::create_kitty(Origin::signed(BOB), KITTY_NAMES[2].as_bytes().to_vec(), Permill::zero()));
+      let (k0, k1, k2) = (CatAuction::kitty_array(0), CatAuction::kitty_array(1), CatAuction::kitty_array(2));
+      let child_name = b"lovely-kitten".to_vec();
+
+      assert_ok!(CatAuction::breed_kitty(Origin::signed(ALICE), k0, k1, child_name.clone()));
+      let child_id = CatAuction::kitty_array(3);
+      let child = CatAuction::kitties(child_id);
+      assert_eq!(child.owner, Some(ALICE));
+      assert_eq!(child.parents, Some((k0, k1)));
+      assert_eq!(child.generation, 1);
+      assert_eq!(child.traits, KittyTraits::from_dna(child.dna.as_ref()));
+
+      // the family tree
+      for parent_id in [k0, k1].iter() {
+        assert_eq!(CatAuction::kitty_children_count(parent_id), 1);
+        assert_eq!(CatAuction::kitty_children((*parent_id, 0)), child_id);
+      }
+
+      // parents are in cooldown
+      assert_eq!(CatAuction::kitties(k0).cooldown_end, BREED_COOLDOWN_BASE);
+      assert_noop!(CatAuction::breed_kitty(Origin::signed(ALICE), k0, k1, child_name.clone()),
+        "Kitty is still in breeding cooldown");
+
+      // breeding with Bob kitty needs his siring approval, which is used once
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(BREED_COOLDOWN_BASE);
+      assert_noop!(CatAuction::breed_kitty(Origin::signed(ALICE), k0, k2, child_name.clone()),
+        "You are not allowed to breed with this kitty");
+      assert_ok!(CatAuction::approve_siring(Origin::signed(BOB), k2, Some(ALICE)));
+      assert_ok!(CatAuction::breed_kitty(Origin::signed(ALICE), k0, k2, child_name.clone()));
+      assert_eq!(CatAuction::siring_approval(k2), None);
+
+      let grandchild_parents = (child_id, CatAuction::kitty_array(4));
+      assert_eq!(CatAuction::kitties(grandchild_parents.1).owner, Some(ALICE));
+      assert_eq!(CatAuction::kitty_children_count(k0), 2);
+
+      // cooldown grows with use
+      assert_eq!(CatAuction::kitties(k0).cooldown_end, BREED_COOLDOWN_BASE + 2 * BREED_COOLDOWN_BASE);
+
+      // next generation
+      assert_ok!(CatAuction::breed_kitty(Origin::signed(ALICE), grandchild_parents.0,
+        grandchild_parents.1, child_name));
+      assert_eq!(CatAuction::kitties(CatAuction::kitty_array(5)).generation, 2);
+    });
+  }
+
+  #[test]
+  fn can_auction_siring_right() {
+    with_externalities(&mut build_ext(), || {
+      assert_ok!(CatAuction::create_kitty(Origin::signed(ALICE), KITTY_NAMES[0].as_bytes().to_vec(), Permill::zero()));
+      assert_ok!(CatAuction::create_kitty(Origin::signed(BOB), KITTY_NAMES[1].as_bytes().to_vec(), Permill::zero()));
+      let (sire_id, bob_kitty_id) = (CatAuction::kitty_array(0), CatAuction::kitty_array(1));
+
+      let end_time = DEFAULT_AUCTION_MIN_DURATION + 5;
+      let right_duration = 1000;
+      assert_ok!(CatAuction::start_siring_auction(Origin::signed(ALICE), sire_id, end_time,
+        BASE_PRICE, PricingRule::FirstPrice, right_duration));
+      let auction_id = CatAuction::auction_array(0);
+
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      assert_ok!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id));
+
+      // Alice keeps the kitty and gets paid, Bob gets the siring right
+      let sire = CatAuction::kitties(sire_id);
+      assert_eq!(sire.owner, Some(ALICE));
+      assert!(!sire.in_auction);
+      assert_eq!(CatAuction::siring_right(sire_id),
+        Some(SiringRight { grantee: BOB, expires: end_time + right_duration }));
+      assert_eq!(<balances::Module<CatAuctionTest>>::free_balance(ALICE), INI_BALANCE + BASE_PRICE);
+
+      // the kitty is locked until the right is used
+      assert_noop!(CatAuction::transfer_kitty(Origin::signed(ALICE), CHARLES, sire_id),
+        "Kitty is locked by an outstanding siring right");
+
+      assert_ok!(CatAuction::breed_kitty(Origin::signed(BOB), bob_kitty_id, sire_id,
+        b"lovely-kitten".to_vec()));
+      assert_eq!(CatAuction::kitties(CatAuction::kitty_array(2)).owner, Some(BOB));
+      assert_eq!(CatAuction::siring_right(sire_id), None);
+      assert_ok!(CatAuction::transfer_kitty(Origin::signed(ALICE), CHARLES, sire_id));
+
+      // a siring right not used is no longer valid after it expires
+      let end_time = end_time + DEFAULT_AUCTION_MIN_DURATION + 5;
+      assert_ok!(CatAuction::start_siring_auction(Origin::signed(CHARLES), sire_id, end_time,
+        BASE_PRICE, PricingRule::FirstPrice, right_duration));
+      let auction_id = CatAuction::auction_array(1);
+      assert_ok!(CatAuction::bid(Origin::signed(BOB), auction_id, BASE_PRICE));
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time);
+      assert_ok!(CatAuction::close_auction_and_tx(Origin::INHERENT, auction_id));
+
+      <timestamp::Module<CatAuctionTest>>::set_timestamp(end_time + right_duration);
+      assert_noop!(CatAuction::breed_kitty(Origin::signed(BOB), bob_kitty_id, sire_id,
+        b"lovely-kitten".to_vec()), "You are not allowed to breed with this kitty");
+      assert_ok!(CatAuction::transfer_kitty(Origin::signed(CHARLES), ALICE, sire_id));
     });
   }
 
   // TODO: Write test cases:
-  //   1. with alice, bob having more than one kitten, and in auction to test
-  //      the kitty switching logic when auction closes and tx happens
-  //   2. with alice starting an auction, Bob, Charles, Dave, and Eve come bid
+  //   1. with alice starting an auction, Bob, Charles, Dave, and Eve come bid
   //      with each one out-bidding each others, and then auction closed.
 }
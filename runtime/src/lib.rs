@@ -20,7 +20,7 @@ use runtime_primitives::{
 };
 use client::{
   block_builder::api::{CheckInherentsResult, InherentData, self as block_builder_api},
-  runtime_api, impl_runtime_apis
+  runtime_api, impl_runtime_apis, decl_runtime_apis
 };
 use version::RuntimeVersion;
 #[cfg(feature = "std")]
@@ -57,7 +57,22 @@ pub type BlockNumber = u64;
 /// Index of an account's extrinsic in the chain.
 pub type Nonce = u64;
 
-mod cat_auction;
+/// The type for recording an account's balance.
+pub type Balance = u128;
+
+/// A timestamp: seconds since the unix epoch.
+pub type Moment = u64;
+
+pub mod cat_auction;
+
+/// Kitty as returned by `CatAuctionApi`.
+pub type Kitty = cat_auction::Kitty<Hash, AccountId, Moment>;
+/// Auction as returned by `CatAuctionApi`.
+pub type Auction = cat_auction::Auction<Hash, Balance, Moment, cat_auction::AuctionTx<Moment, AccountId, Balance>>;
+/// Bid as returned by `CatAuctionApi`.
+pub type Bid = cat_auction::Bid<Hash, AccountId, Balance, Moment>;
+/// Auction with its kitty and displayed bids as returned by `CatAuctionApi`.
+pub type AuctionDetail = cat_auction::AuctionDetail<Kitty, Auction, Bid>;
 
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
@@ -96,8 +111,8 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
   spec_name: create_runtime_str!("ha"),
   impl_name: create_runtime_str!("ha"),
   authoring_version: 3,
-  spec_version: 4,
-  impl_version: 4,
+  spec_version: 7,
+  impl_version: 7,
   apis: RUNTIME_API_VERSIONS,
 };
 
@@ -163,13 +178,13 @@ impl indices::Trait for Runtime {
 
 impl timestamp::Trait for Runtime {
   /// A timestamp: seconds since the unix epoch.
-  type Moment = u64;
+  type Moment = Moment;
   type OnTimestampSet = Aura;
 }
 
 impl balances::Trait for Runtime {
   /// The type for recording an account's balance.
-  type Balance = u128;
+  type Balance = Balance;
   /// What to do if an account's free balance gets zeroed.
   type OnFreeBalanceZero = ();
   /// What to do if a new account is created.
@@ -206,7 +221,7 @@ construct_runtime!(
     Balances: balances,
     Sudo: sudo,
 
-    CatAuction: cat_auction::{ Module, Call, Storage, Event<T> },
+    CatAuction: cat_auction::{ Module, Call, Storage, Event<T>, Config<T> },
   }
 );
 
@@ -227,6 +242,26 @@ pub type CheckedExtrinsic = generic::CheckedExtrinsic<AccountId, Nonce, Call>;
 /// Executive: handles dispatch to the various modules.
 pub type Executive = executive::Executive<Runtime, Block, Context, Balances, AllModules>;
 
+decl_runtime_apis! {
+  /// Queries of kitties, auctions and bids in the cat auction module. They return assembled
+  /// structs, so clients do not depend on the module storage layout. Paged queries return
+  /// at most 100 items from `offset`.
+  pub trait CatAuctionApi {
+    /// Kitties of `owner`, in the order they are added to the owner.
+    fn kitties_of(owner: AccountId, offset: u64, limit: u64) -> Vec<Kitty>;
+    /// Ongoing auctions. Closing or cancelling an auction moves the last started one to its place.
+    fn ongoing_auctions(offset: u64, limit: u64) -> Vec<Auction>;
+    /// An auction with its kitty and displayed bids.
+    fn auction_detail(auction_id: Hash) -> Option<AuctionDetail>;
+    /// A kitty by its ID.
+    fn kitty(kitty_id: Hash) -> Option<Kitty>;
+    /// Bids of `account` not withdrawn. Withdrawing a bid moves the last placed one to its place.
+    fn bids_of(account: AccountId, offset: u64, limit: u64) -> Vec<Bid>;
+    /// Codes and messages of the errors auction calls are rejected with.
+    fn errors() -> Vec<(u8, Vec<u8>)>;
+  }
+}
+
 // Implement our runtime API endpoints. This is just a bunch of proxying.
 impl_runtime_apis! {
   impl runtime_api::Core<Block> for Runtime {
@@ -298,4 +333,32 @@ impl_runtime_apis! {
       Consensus::authorities()
     }
   }
+
+  impl self::CatAuctionApi<Block> for Runtime {
+    fn kitties_of(owner: AccountId, offset: u64, limit: u64) -> Vec<Kitty> {
+      CatAuction::kitties_of(&owner, offset, limit)
+    }
+
+    fn ongoing_auctions(offset: u64, limit: u64) -> Vec<Auction> {
+      CatAuction::ongoing_auctions(offset, limit)
+    }
+
+    fn auction_detail(auction_id: Hash) -> Option<AuctionDetail> {
+      CatAuction::auction_detail(auction_id)
+    }
+
+    fn kitty(kitty_id: Hash) -> Option<Kitty> {
+      CatAuction::find_kitty(kitty_id)
+    }
+
+    fn bids_of(account: AccountId, offset: u64, limit: u64) -> Vec<Bid> {
+      CatAuction::bids_of(&account, offset, limit)
+    }
+
+    fn errors() -> Vec<(u8, Vec<u8>)> {
+      cat_auction::Error::ALL.iter()
+        .map(|err| (err.code(), err.message().as_bytes().to_vec()))
+        .collect()
+    }
+  }
 }
@@ -1,7 +1,7 @@
-use primitives::{ed25519, sr25519, Pair};
+use primitives::{ed25519, sr25519, Pair, blake2_256};
 use ha_runtime::{
-	AccountId, GenesisConfig, ConsensusConfig, TimestampConfig, BalancesConfig,
-	SudoConfig, IndicesConfig,
+	AccountId, Hash, GenesisConfig, ConsensusConfig, TimestampConfig, BalancesConfig,
+	SudoConfig, IndicesConfig, CatAuctionConfig,
 };
 use substrate_service;
 
@@ -10,6 +10,11 @@ use ed25519::Public as AuthorityId;
 // Note this is the URL for the telemetry server
 //const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
 
+/// Names of the kitties every testnet starts with. They are dealt to the endowed accounts in turn.
+const GENESIS_KITTY_NAMES: [&str; 8] = [
+	"Garfield", "Tom", "Felix", "Salem", "Duchess", "Cheshire", "Grumpy", "Nyan",
+];
+
 /// Specialized `ChainSpec`. This is a specialization of the general Substrate ChainSpec type.
 pub type ChainSpec = substrate_service::ChainSpec<GenesisConfig>;
 
@@ -90,6 +95,15 @@ impl Alternative {
 	}
 }
 
+/// Kitties minted at genesis, as (owner, name, dna). The DNA is derived from the name, so
+/// every chain started from the same spec has the same catalog.
+fn genesis_kitties(owners: &[AccountId]) -> Vec<(AccountId, Vec<u8>, Hash)> {
+	GENESIS_KITTY_NAMES.iter()
+		.zip(owners.iter().cycle())
+		.map(|(name, owner)| (owner.clone(), name.as_bytes().to_vec(), Hash::from(blake2_256(name.as_bytes()))))
+		.collect()
+}
+
 fn testnet_genesis(initial_authorities: Vec<AuthorityId>, endowed_accounts: Vec<AccountId>, root_key: AccountId) -> GenesisConfig {
 	GenesisConfig {
 		consensus: Some(ConsensusConfig {
@@ -115,5 +129,11 @@ fn testnet_genesis(initial_authorities: Vec<AuthorityId>, endowed_accounts: Vec<
 		sudo: Some(SudoConfig {
 			key: root_key,
 		}),
+		cat_auction: Some(CatAuctionConfig {
+			kitties: genesis_kitties(&endowed_accounts),
+			topmost_bids_len: 3,
+			auction_min_duration: 3 * 60,
+			display_bids_update_period: 60,
+		}),
 	}
 }
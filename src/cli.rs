@@ -1,13 +1,35 @@
-use crate::service;
+use crate::{service, rpc};
 use futures::{future, Future, sync::oneshot};
 use std::cell::RefCell;
 use tokio::runtime::Runtime;
 pub use substrate_cli::{VersionInfo, IntoExit, error};
-use substrate_cli::{informant, parse_and_execute, NoCustom};
+use substrate_cli::{informant, parse_and_execute, impl_augment_clap, NoCustom};
 use substrate_service::{ServiceFactory, Roles as ServiceRoles};
 use crate::chain_spec;
 use std::ops::Deref;
-use log::info;
+use log::{info, warn};
+use std::net::{Ipv4Addr, SocketAddr};
+use structopt::StructOpt;
+
+/// Options of the auction JSON-RPC server.
+#[derive(Debug, StructOpt, Clone)]
+pub struct AuctionRpcParams {
+	/// Specify the auction JSON-RPC server TCP port. It listens on the interface of the node HTTP
+	/// RPC server, so `--rpc-external` exposes it as well
+	#[structopt(long = "auction-rpc-port", value_name = "PORT")]
+	auction_rpc_port: Option<u16>,
+
+	/// Do not start the auction JSON-RPC server
+	#[structopt(long = "no-auction-rpc")]
+	no_auction_rpc: bool,
+
+	/// Origins allowed to call the auction JSON-RPC server from a browser: a comma separated list,
+	/// or `all`. Local origins by default
+	#[structopt(long = "auction-rpc-cors", value_name = "ORIGINS")]
+	auction_rpc_cors: Option<String>,
+}
+
+impl_augment_clap!(AuctionRpcParams);
 
 /// Parse command line arguments into service configuration.
 pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()> where
@@ -15,9 +37,9 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 	T: Into<std::ffi::OsString> + Clone,
 	E: IntoExit,
 {
-	parse_and_execute::<service::Factory, NoCustom, NoCustom, _, _, _, _, _>(
+	parse_and_execute::<service::Factory, NoCustom, AuctionRpcParams, _, _, _, _, _>(
 		load_spec, &version, "substrate-node", args, exit,
-	 	|exit, _custom_args, config| {
+	 	|exit, auction_rpc: AuctionRpcParams, config| {
 			info!("{}", version.name);
 			info!("  version {}", config.full_version());
 			info!("  by {}, 2017, 2018", version.author);
@@ -32,16 +54,46 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 				 	service::Factory::new_light(config, executor).map_err(|e| format!("{:?}", e))?,
 					exit
 				),
-				_ => run_until_exit(
-					runtime,
-					service::Factory::new_full(config, executor).map_err(|e| format!("{:?}", e))?,
-					exit
-				),
+				_ => {
+					let auction_rpc_addr = if auction_rpc.no_auction_rpc {
+						None
+					} else {
+						let ip = config.rpc_http.map_or(Ipv4Addr::LOCALHOST.into(), |addr| addr.ip());
+						let port = auction_rpc.auction_rpc_port.unwrap_or(rpc::DEFAULT_HTTP_PORT);
+						Some(SocketAddr::new(ip, port))
+					};
+					let auction_rpc_cors = rpc::parse_cors(
+						auction_rpc.auction_rpc_cors.as_ref().map(String::as_str));
+					let service = service::Factory::new_full(config, executor).map_err(|e| format!("{:?}", e))?;
+					// kept alive until the node exits
+					let _auction_rpc = auction_rpc_addr
+						.and_then(|addr| start_auction_rpc(&service, &addr, auction_rpc_cors));
+					run_until_exit(runtime, service, exit)
+				},
 			}.map_err(|e| format!("{:?}", e))
 		}
 	).map_err(Into::into).map(|_| ())
 }
 
+// serve the `auction_*` JSON-RPC methods on `addr`. The node keeps running without them when
+// the server cannot be started
+fn start_auction_rpc(
+	service: &substrate_service::Service<substrate_service::FullComponents<service::Factory>>,
+	addr: &SocketAddr,
+	cors: Option<Vec<String>>,
+) -> Option<jsonrpc_http_server::Server> {
+	match rpc::start_http(service.client(), addr, cors) {
+		Ok(server) => {
+			info!("Auction JSON-RPC listening on http://{}", addr);
+			Some(server)
+		},
+		Err(e) => {
+			warn!("Unable to start auction JSON-RPC on {}: {:?}", addr, e);
+			None
+		},
+	}
+}
+
 fn load_spec(id: &str) -> Result<Option<chain_spec::ChainSpec>, String> {
 	Ok(match chain_spec::Alternative::from(id) {
 		Some(spec) => Some(spec.load()?),
@@ -0,0 +1,147 @@
+//! JSON-RPC methods of the cat auction module, served by the full client.
+//!
+//! The `auction_*` methods are answered through the `CatAuctionApi` runtime API at the best
+//! block, so clients get assembled kitties, auctions and bids instead of decoding storage.
+
+use std::{io, net::SocketAddr, sync::Arc};
+use jsonrpc_core::{Error, ErrorCode, IoHandler, Result};
+use jsonrpc_derive::rpc;
+use jsonrpc_http_server::{DomainsValidation, Server, ServerBuilder};
+use ha_runtime::{
+	AccountId, Auction, AuctionDetail, Bid, CatAuctionApi, Hash, Kitty, opaque::BlockId,
+};
+use substrate_client::runtime_api::ProvideRuntimeApi;
+use substrate_service::FullClient;
+use crate::service::Factory;
+
+/// Default port of the auction JSON-RPC server. The node HTTP and WS RPC servers default to
+/// 9933 and 9944.
+pub const DEFAULT_HTTP_PORT: u16 = 9935;
+
+/// Origins allowed by default: browser apps served from the local machine.
+const DEFAULT_CORS: &[&str] = &[
+	"http://localhost:*", "http://127.0.0.1:*", "https://localhost:*", "https://127.0.0.1:*",
+];
+
+/// Auction module JSON-RPC methods.
+#[rpc]
+pub trait AuctionApi {
+	/// Ongoing auctions. At most 100 from `offset`.
+	#[rpc(name = "auction_listOngoing")]
+	fn list_ongoing(&self, offset: u64, limit: u64) -> Result<Vec<Auction>>;
+
+	/// An auction with its kitty and displayed bids.
+	#[rpc(name = "auction_getAuction")]
+	fn get_auction(&self, auction_id: Hash) -> Result<Option<AuctionDetail>>;
+
+	/// A kitty by its ID.
+	#[rpc(name = "auction_getKitty")]
+	fn get_kitty(&self, kitty_id: Hash) -> Result<Option<Kitty>>;
+
+	/// Kitties of `owner`. At most 100 from `offset`.
+	#[rpc(name = "auction_kittiesOf")]
+	fn kitties_of(&self, owner: AccountId, offset: u64, limit: u64) -> Result<Vec<Kitty>>;
+
+	/// Bids of `account` not withdrawn. At most 100 from `offset`.
+	#[rpc(name = "auction_bidsOf")]
+	fn bids_of(&self, account: AccountId, offset: u64, limit: u64) -> Result<Vec<Bid>>;
+}
+
+/// Auction module JSON-RPC methods served by the full client.
+pub struct AuctionRpc {
+	client: Arc<FullClient<Factory>>,
+}
+
+impl AuctionRpc {
+	/// Create the methods over the full client.
+	pub fn new(client: Arc<FullClient<Factory>>) -> Self {
+		AuctionRpc { client }
+	}
+
+	fn best_block(&self) -> Result<BlockId> {
+		let info = self.client.info().map_err(client_error)?;
+		Ok(BlockId::hash(info.chain.best_hash))
+	}
+}
+
+impl AuctionApi for AuctionRpc {
+	fn list_ongoing(&self, offset: u64, limit: u64) -> Result<Vec<Auction>> {
+		let at = self.best_block()?;
+		self.client.runtime_api().ongoing_auctions(&at, offset, limit).map_err(client_error)
+	}
+
+	fn get_auction(&self, auction_id: Hash) -> Result<Option<AuctionDetail>> {
+		let at = self.best_block()?;
+		self.client.runtime_api().auction_detail(&at, auction_id).map_err(client_error)
+	}
+
+	fn get_kitty(&self, kitty_id: Hash) -> Result<Option<Kitty>> {
+		let at = self.best_block()?;
+		self.client.runtime_api().kitty(&at, kitty_id).map_err(client_error)
+	}
+
+	fn kitties_of(&self, owner: AccountId, offset: u64, limit: u64) -> Result<Vec<Kitty>> {
+		let at = self.best_block()?;
+		self.client.runtime_api().kitties_of(&at, owner, offset, limit).map_err(client_error)
+	}
+
+	fn bids_of(&self, account: AccountId, offset: u64, limit: u64) -> Result<Vec<Bid>> {
+		let at = self.best_block()?;
+		self.client.runtime_api().bids_of(&at, account, offset, limit).map_err(client_error)
+	}
+}
+
+fn client_error<E: std::fmt::Debug>(err: E) -> Error {
+	Error {
+		code: ErrorCode::ServerError(1),
+		message: "Unable to query the cat auction runtime API".into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
+
+/// Start an HTTP server for the auction methods. The server stops when it is dropped.
+///
+/// `cors` lists the origins browsers may call the methods from, `None` allowing any origin.
+/// A server on a loopback interface only answers requests addressed to it by a local host
+/// name, against DNS rebinding.
+pub fn start_http(
+	client: Arc<FullClient<Factory>>,
+	addr: &SocketAddr,
+	cors: Option<Vec<String>>,
+) -> io::Result<Server> {
+	let mut io = IoHandler::new();
+	io.extend_with(AuctionRpc::new(client).to_delegate());
+
+	let cors = match cors {
+		Some(origins) => DomainsValidation::AllowOnly(origins.into_iter().map(Into::into).collect()),
+		None => DomainsValidation::Disabled,
+	};
+	let hosts = if addr.ip().is_loopback() {
+		let port = addr.port();
+		DomainsValidation::AllowOnly(vec![
+			format!("localhost:{}", port).into(),
+			format!("127.0.0.1:{}", port).into(),
+		])
+	} else {
+		DomainsValidation::Disabled
+	};
+
+	ServerBuilder::new(io)
+		.threads(1)
+		.cors(cors)
+		.allowed_hosts(hosts)
+		.start_http(addr)
+}
+
+/// Origins of `--auction-rpc-cors`: `all` for any origin, otherwise a comma separated list.
+/// Local origins when the flag is not set.
+pub fn parse_cors(cors: Option<&str>) -> Option<Vec<String>> {
+	match cors {
+		Some("all") => None,
+		Some(origins) => Some(origins.split(',')
+			.map(|origin| origin.trim().to_owned())
+			.filter(|origin| !origin.is_empty())
+			.collect()),
+		None => Some(DEFAULT_CORS.iter().map(|o| (*o).to_owned()).collect()),
+	}
+}